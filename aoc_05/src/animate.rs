@@ -0,0 +1,127 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Time between redraws of the animation.
+const FRAME: Duration = Duration::from_millis(50);
+
+const HEX: &[u8] = b"0123456789abcdef";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+const CLEAR_LINE: &str = "\x1b[K";
+const UP_TWO_LINES: &str = "\x1b[2A";
+
+// Xorshift, random enough for flickering characters.
+struct Rng(u64);
+
+impl Rng {
+    fn hex(&mut self) -> char {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        HEX[(self.0 % 16) as usize] as char
+    }
+}
+
+struct State {
+    one: Vec<char>,
+    two: Vec<char>,
+    done: bool,
+}
+
+// Unknown characters are ' ' in the passwords.
+fn line(name: &str, password: &[char], rng: Option<&mut Rng>) -> String {
+    match rng {
+        Some(rng) => {
+            let chars: String = password
+                .iter()
+                .map(|&c| match c {
+                    ' ' => format!("{}{}{}", DIM, rng.hex(), RESET),
+                    c => format!("{}{}{}", BOLD, c, RESET),
+                })
+                .collect();
+            format!("password {}: {}", name, chars)
+        }
+        None => format!("password {}: {}", name, password.iter().map(|&c| if c == ' ' { '_' } else { c }).collect::<String>()),
+    }
+}
+
+// Shows the passwords as they are found. On a terminal both are redrawn in place over and over,
+// with random characters where they aren't known yet. Otherwise every hit gets a line of its own.
+pub struct Progress {
+    // The state shared with the thread drawing the animation, None when not on a terminal.
+    cinematic: Option<(Arc<Mutex<State>>, JoinHandle<()>)>,
+}
+
+impl Progress {
+    pub fn new(password_len: usize) -> Progress {
+        if !io::stdout().is_terminal() {
+            return Progress { cinematic: None };
+        }
+        let state = Arc::new(Mutex::new(State {
+            one: vec![' '; password_len],
+            two: vec![' '; password_len],
+            done: false,
+        }));
+        let shared = state.clone();
+        let drawer = thread::spawn(move || {
+            let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64);
+            let mut rng = Rng(seed | 1);
+            let stdout = io::stdout();
+            let mut first = true;
+            loop {
+                let state = shared.lock().expect("Animation state poisoned.");
+                // The last frame is drawn without any random characters.
+                let (one, two) = if state.done {
+                    (line("one", &state.one, None), line("two", &state.two, None))
+                } else {
+                    (line("one", &state.one, Some(&mut rng)), line("two", &state.two, Some(&mut rng)))
+                };
+                let up = if first { "" } else { UP_TWO_LINES };
+                first = false;
+                let mut out = stdout.lock();
+                let drawn = write!(out, "{}\r{}{}\n\r{}{}\n", up, one, CLEAR_LINE, two, CLEAR_LINE).and_then(|_| out.flush());
+                // Once the terminal is gone there is nothing left to draw on, the search carries on
+                // without the animation.
+                if drawn.is_err() || state.done {
+                    break;
+                }
+                drop(state);
+                thread::sleep(FRAME);
+            }
+        });
+        Progress {
+            cinematic: Some((state, drawer)),
+        }
+    }
+
+    // Called for every hit at `index`, with the passwords so far.
+    pub fn update(&self, index: u64, one: &[char], two: &[char]) {
+        match self.cinematic {
+            Some((ref state, _)) => {
+                let mut state = state.lock().expect("Animation state poisoned.");
+                state.one = one.to_vec();
+                state.two = two.to_vec();
+            }
+            None => println!("{}: {}, {}", index, line("one", one, None), line("two", two, None)),
+        }
+    }
+
+    // Shows the final passwords.
+    pub fn finish(self, one: &[char], two: &[char]) {
+        match self.cinematic {
+            Some((state, drawer)) => {
+                {
+                    let mut state = state.lock().expect("Animation state poisoned.");
+                    state.one = one.to_vec();
+                    state.two = two.to_vec();
+                    state.done = true;
+                }
+                drawer.join().expect("Animation thread panicked.");
+            }
+            None => println!("{}\n{}", line("one", one, None), line("two", two, None)),
+        }
+    }
+}
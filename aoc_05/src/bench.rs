@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use fallback;
+use hash::{self, Backend};
+use search::{self, Hit, CHUNK_SIZE};
+
+// The search the way it was first written, a new string for every index and the whole hash in
+// hex before looking at it.
+fn naive_chunk(door_id: &str, zeros: usize, start: u64) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    for index in start..start + CHUNK_SIZE {
+        let input = door_id.to_string() + &index.to_string();
+        let mut h = hash::Md5::new();
+        h.update(input.as_bytes());
+        let digest = hash::hex(&h.finish());
+        if digest[0..zeros].chars().all(|c| c == '0') {
+            hits.push(Hit { index, digest });
+        }
+    }
+    hits
+}
+
+fn time<F: Fn(&str, usize, u64) -> Vec<Hit>>(search: F, door_id: &str, zeros: usize, chunks: u64) -> (Vec<Hit>, Duration) {
+    let start = Instant::now();
+    let hits = (0..chunks).flat_map(|chunk| search(door_id, zeros, chunk * CHUNK_SIZE)).collect();
+    (hits, start.elapsed())
+}
+
+fn time_threads(door_id: &str, zeros: usize, threads: usize, indices: u64) -> (Vec<Hit>, Duration) {
+    let start = Instant::now();
+    let hits = search::hits_between(door_id, zeros, threads, 0, indices).collect();
+    (hits, start.elapsed())
+}
+
+// Hashes the first `count` indices, rounded up to whole chunks, on one thread both the naive way
+// and the way the search does it with every backend, then searches them again with one thread and
+// with `threads`, and prints how long each took. None if they don't all find the same hashes.
+pub fn run(door_id: &str, zeros: usize, count: u64, threads: usize) -> Option<()> {
+    let chunks = count.div_ceil(CHUNK_SIZE).max(1);
+    let indices = chunks * CHUNK_SIZE;
+    let (naive_hits, naive) = time(naive_chunk, door_id, zeros, chunks);
+    #[allow(unused_mut)]
+    let mut runs = vec![("fallback", time(search::search_chunk::<fallback::Md5>, door_id, zeros, chunks))];
+    #[cfg(feature = "md-5")]
+    runs.push(("md-5", time(search::search_chunk::<hash::Md5>, door_id, zeros, chunks)));
+    let one_thread = time_threads(door_id, zeros, 1, indices);
+    let all_threads = time_threads(door_id, zeros, threads, indices);
+    let searches = [&one_thread.0, &all_threads.0];
+    if runs.iter().any(|&(_, (ref hits, _))| *hits != naive_hits) || searches.iter().any(|&hits| *hits != naive_hits) {
+        return None;
+    }
+
+    let rate = |elapsed: Duration| indices as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("Hashed {} indices of door {}, {} hits with {} zeroes", indices, door_id, naive_hits.len(), zeros);
+    println!("{:<16}{:>8.3}s  {:>6.2}M indices/s", format!("naive ({}):", hash::NAME), naive.as_secs_f64(), rate(naive));
+    for (name, (_, elapsed)) in runs {
+        println!(
+            "{:<16}{:>8.3}s  {:>6.2}M indices/s  {:.2}x faster",
+            format!("{}:", name),
+            elapsed.as_secs_f64(),
+            rate(elapsed),
+            naive.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+    let (one, all) = (one_thread.1, all_threads.1);
+    println!("{:<16}{:>8.3}s  {:>6.2}M indices/s", "1 thread:", one.as_secs_f64(), rate(one));
+    println!(
+        "{:<16}{:>8.3}s  {:>6.2}M indices/s  {:.2}x faster",
+        format!("{} thread{}:", threads, if threads == 1 { "" } else { "s" }),
+        all.as_secs_f64(),
+        rate(all),
+        one.as_secs_f64() / all.as_secs_f64()
+    );
+    Some(())
+}
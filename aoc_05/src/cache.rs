@@ -0,0 +1,102 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use search::Hit;
+
+// What earlier runs found for one door ID and number of zeroes. The file is a log of lines that
+// only ever gets appended to:
+//
+//   hit INDEX DIGEST
+//   searched INDEX
+//
+// where every index below the last `searched` one has been hashed and all hits among them are
+// in the file.
+pub struct Cache {
+    file: File,
+    searched: u64,
+    hits: Vec<Hit>,
+}
+
+// Door IDs are used in file names as they are, apart from characters that don't belong there.
+fn file_name(door_id: &str, zeros: usize) -> String {
+    let mut name = String::new();
+    for b in door_id.bytes() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+            name.push(b as char);
+        } else {
+            name.push_str(&format!("%{:02x}", b));
+        }
+    }
+    format!("{}.{}", name, zeros)
+}
+
+fn parse_line(line: &str, zeros: usize) -> Option<Result<Hit, u64>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words[..] {
+        ["hit", index, digest] if digest.len() == 32 && digest.starts_with(&"0".repeat(zeros)) => {
+            Some(Ok(Hit { index: index.parse().ok()?, digest: digest.to_string() }))
+        }
+        ["searched", index] => Some(Err(index.parse().ok()?)),
+        _ => None,
+    }
+}
+
+fn load(path: &Path, zeros: usize) -> io::Result<(u64, Vec<Hit>)> {
+    let mut searched = 0;
+    let mut hits = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        match parse_line(&line?, zeros) {
+            Some(Ok(hit)) => hits.push(hit),
+            Some(Err(index)) => searched = searched.max(index),
+            None => {
+                let error = format!("{}: line {} is broken", path.display(), i + 1);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+            }
+        }
+    }
+    // Hits past the last index known to be searched may have gaps before them.
+    hits.retain(|hit| hit.index < searched);
+    hits.sort_by_key(|hit| hit.index);
+    hits.dedup();
+    Ok((searched, hits))
+}
+
+impl Cache {
+    // Opens the cache for `door_id` and `zeros` in `dir`, creating it if there isn't one yet.
+    pub fn open(dir: &Path, door_id: &str, zeros: usize) -> io::Result<Cache> {
+        fs::create_dir_all(dir)?;
+        let path: PathBuf = dir.join(file_name(door_id, zeros));
+        let (searched, hits) = match load(&path, zeros) {
+            Ok(loaded) => loaded,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (0, Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Cache { file, searched, hits })
+    }
+
+    // Every index below this one has been searched before.
+    pub fn searched(&self) -> u64 {
+        self.searched
+    }
+
+    // The hits below `searched()`, in index order.
+    pub fn take_hits(&mut self) -> Vec<Hit> {
+        self.hits.split_off(0)
+    }
+
+    pub fn add(&mut self, hit: &Hit) -> io::Result<()> {
+        writeln!(self.file, "hit {} {}", hit.index, hit.digest)
+    }
+
+    // Records that every index below `index` has been searched, and that every hit among them
+    // has been added.
+    pub fn set_searched(&mut self, index: u64) -> io::Result<()> {
+        if index <= self.searched {
+            return Ok(());
+        }
+        self.searched = index;
+        writeln!(self.file, "searched {}", index)
+    }
+}
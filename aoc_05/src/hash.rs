@@ -0,0 +1,102 @@
+#[cfg(feature = "md-5")]
+use md5;
+
+use fallback;
+
+// An MD5 implementation to search with. A clone of one that has been given part of the input
+// carries on from there, without hashing that part again.
+pub trait Backend: Clone + Send {
+    fn new() -> Self;
+    fn update(&mut self, input: &[u8]);
+    fn finish(self) -> [u8; 16];
+}
+
+#[cfg(feature = "md-5")]
+impl Backend for md5::Md5 {
+    fn new() -> md5::Md5 {
+        md5::Digest::new()
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        md5::Digest::update(self, input)
+    }
+
+    fn finish(self) -> [u8; 16] {
+        md5::Digest::finalize(self).into()
+    }
+}
+
+// The backend the search uses, the md-5 crate unless it's built without the md-5 feature.
+#[cfg(feature = "md-5")]
+pub type Md5 = md5::Md5;
+#[cfg(feature = "md-5")]
+pub const NAME: &str = "md-5";
+#[cfg(not(feature = "md-5"))]
+pub type Md5 = fallback::Md5;
+#[cfg(not(feature = "md-5"))]
+pub const NAME: &str = "fallback";
+
+pub fn hex(digest: &[u8; 16]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The hashes from the puzzle, and a few more for inputs of a block or more.
+const VECTORS: [(&str, &str); 8] = [
+    ("abc3231929", "00000155f8105dff7f56ee10fa9b9abd"),
+    ("abc5017308", "000008f82c5b3924a1ecbebf60344e00"),
+    ("abc5278568", "00000f9a2c309875e05c5a5d09f1b8c4"),
+    ("abc5357525", "000004e597bd77c5cd2133e9d885fe7e"),
+    ("", "d41d8cd98f00b204e9800998ecf8427e"),
+    ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+    ("The quick brown fox jumps over the lazy dog", "9e107d9d372bb6826bd81d3542a419d6"),
+    (
+        "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+        "57edf4a22be3c955ac49da2e2107b67a",
+    ),
+];
+
+// Hashes every vector with `B`, both in one go and the way the search does, from a clone of the
+// state after the first part. Returns what came out wrong.
+fn check_backend<B: Backend>() -> Vec<String> {
+    let mut wrong = Vec::new();
+    for &(input, expected) in &VECTORS {
+        let mut whole = B::new();
+        whole.update(input.as_bytes());
+        let split = input.len() / 3;
+        let mut prefix = B::new();
+        prefix.update(&input.as_bytes()[..split]);
+        let mut rest = prefix.clone();
+        rest.update(&input.as_bytes()[split..]);
+        for digest in [hex(&whole.finish()), hex(&rest.finish())] {
+            if digest != expected {
+                wrong.push(format!("md5({:?}) is {}, not {}", input, digest, expected));
+            }
+        }
+    }
+    wrong
+}
+
+// Checks every backend that is built in, returning their names along with what they got wrong.
+pub fn check() -> Vec<(&'static str, Vec<String>)> {
+    #[allow(unused_mut)]
+    let mut results = vec![("fallback", check_backend::<fallback::Md5>())];
+    #[cfg(feature = "md-5")]
+    results.push(("md-5", check_backend::<md5::Md5>()));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_hashes_the_vectors() {
+        assert_eq!(check_backend::<fallback::Md5>(), Vec::<String>::new());
+    }
+
+    #[cfg(feature = "md-5")]
+    #[test]
+    fn md5_hashes_the_vectors() {
+        assert_eq!(check_backend::<md5::Md5>(), Vec::<String>::new());
+    }
+}
@@ -57,79 +57,212 @@
 //
 // Your puzzle input is still ffykfhsq.
 
-use std::io::{self, Write};
-extern crate crypto;
-use crypto::md5;
-use crypto::digest::Digest;
+#[cfg(feature = "md-5")]
+extern crate md5;
 
-const DOOR_ID: &'static str = "ffykfhsq";
+mod animate;
+mod bench;
+mod cache;
+mod fallback;
+mod hash;
+mod search;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+const DOOR_ID: &str = "ffykfhsq";
+const ZEROS: usize = 5;
 const PASSWORD_LEN: usize = 8;
+// The position in the second password is a single hex digit.
+const MAX_PASSWORD_LEN: usize = 16;
+// The hash has 32 hex digits, the last two after the zeroes are the password characters.
+const MAX_ZEROS: usize = 30;
+const CACHE_DIR: &str = ".cache";
+
+struct Options {
+    door_id: String,
+    zeros: usize,
+    password_len: usize,
+    threads: usize,
+    // None when the cache is off.
+    cache_dir: Option<PathBuf>,
+    // Indices to hash with --bench, instead of looking for the passwords.
+    bench: Option<u64>,
+    // Whether to check the MD5 backends instead.
+    check: bool,
+}
+
+fn usage(prog_name: &str) {
+    eprintln!("{} [--door-id ID] [--zeros N] [--length N] [--threads N] [--cache DIR|off]", prog_name);
+    eprintln!("{} [--door-id ID] [--zeros N] [--threads N] --bench N", prog_name);
+    eprintln!("{} --check", prog_name);
+    eprintln!();
+    eprintln!("Finds the passwords for door {} with hashes that start with {} zeroes and", DOOR_ID, ZEROS);
+    eprintln!("passwords {} characters long, unless the options say otherwise. The length has to be", PASSWORD_LEN);
+    eprintln!("between 1 and {} and there can't be more than {} zeroes. Searches with one thread per", MAX_PASSWORD_LEN, MAX_ZEROS);
+    eprintln!("core unless --threads says otherwise. The passwords are decrypted in place when");
+    eprintln!("writing to a terminal, otherwise every hash that helps gets a line of its own.");
+    eprintln!();
+    eprintln!("The hashes found are kept in {} for every door and number of zeroes, unless", CACHE_DIR);
+    eprintln!("--cache says otherwise, and later runs pick up where the search left off.");
+    eprintln!();
+    eprintln!("With --bench the first N indices are hashed on one thread both the way the search");
+    eprintln!("was first written and the way it is done now, to see how long each takes. Then the");
+    eprintln!("search goes over them with one thread and with every thread, to see how well it scales.");
+    eprintln!();
+    eprintln!("Hashes with the {} MD5 backend. The md-5 crate is used unless the md-5 feature is", hash::NAME);
+    eprintln!("turned off, then a built-in fallback is. --check hashes the example from the puzzle,");
+    eprintln!("and a few more, with every backend that's built in.");
+}
 
-fn has_password_char(hash: &str) -> bool {
-    for c in hash[0..5].chars() {
-        if c != '0' {
-            return false;
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut options = Options {
+        door_id: DOOR_ID.to_string(),
+        zeros: ZEROS,
+        password_len: PASSWORD_LEN,
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        cache_dir: Some(PathBuf::from(CACHE_DIR)),
+        bench: None,
+        check: false,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--check" {
+            options.check = true;
+            continue;
+        }
+        let value = args.next()?;
+        match arg.as_str() {
+            "--door-id" => options.door_id = value.clone(),
+            "--zeros" => options.zeros = value.parse().ok().filter(|&n| n <= MAX_ZEROS)?,
+            "--length" => options.password_len = value.parse().ok().filter(|&n| n > 0 && n <= MAX_PASSWORD_LEN)?,
+            "--threads" => options.threads = value.parse().ok().filter(|&n| n > 0)?,
+            "--cache" if value == "off" => options.cache_dir = None,
+            "--cache" => options.cache_dir = Some(PathBuf::from(value)),
+            "--bench" => options.bench = Some(value.parse().ok().filter(|&n| n > 0)?),
+            _ => return None,
         }
     }
-    return true;
+    Some(options)
 }
 
 fn main() {
-    let mut password_one: Vec<char> = vec![' '; PASSWORD_LEN];
-    let mut password_two: Vec<char> = vec![' '; PASSWORD_LEN];
+    let args: Vec<String> = env::args().collect();
+    let options = parse_args(&args[1..]).unwrap_or_else(|| {
+        usage(&args[0]);
+        process::exit(2);
+    });
+    let (zeros, password_len) = (options.zeros, options.password_len);
+    if options.check {
+        let mut ok = true;
+        for (name, wrong) in hash::check() {
+            println!("{}: {}", name, if wrong.is_empty() { "ok" } else { "wrong" });
+            for problem in &wrong {
+                println!("  {}", problem);
+            }
+            ok &= wrong.is_empty();
+        }
+        process::exit(if ok { 0 } else { 1 });
+    }
+    if let Some(count) = options.bench {
+        if bench::run(&options.door_id, zeros, count, options.threads).is_none() {
+            eprintln!("The naive search and the search found different hashes.");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let mut password_one: Vec<char> = vec![' '; password_len];
+    let mut password_two: Vec<char> = vec![' '; password_len];
 
     let mut password_one_found = false;
     let mut password_two_found = false;
     let mut password_one_pos: usize = 0;
     let mut password_two_valid_chars = 0;
 
-    let mut h = md5::Md5::new();
-    let mut count = 0;
-
-    while !(password_one_found && password_two_found) {
-        let input = DOOR_ID.to_string() + &count.to_string();
-        h.input_str(&input);
-        let digest = h.result_str();
-        if has_password_char(&digest) {
-            print!(".");
-            io::stdout().flush().unwrap();
-            // Fifth char used to find both password:
-            // - Char in first password
-            // - Position of char in second password
-            let c = match digest.chars().nth(5) {
-                Some(c) => c,
-                None => panic!("Bork. Bork.")
-            };
+    let mut cache = options.cache_dir.as_ref().map(|dir| {
+        cache::Cache::open(dir, &options.door_id, zeros).unwrap_or_else(|e| {
+            eprintln!("Can't use the cache in {}: {}", dir.display(), e);
+            process::exit(1);
+        })
+    });
+    let cached = cache.as_mut().map_or(Vec::new(), |cache| cache.take_hits());
+    let resume_at = cache.as_ref().map_or(0, |cache| cache.searched());
 
-            if !password_one_found {
-                password_one[password_one_pos] = c;
-                password_one_pos += 1;
-                password_one_found = password_one_pos == PASSWORD_LEN;
-            }
-            if !password_two_found {
-                let pos = match c.to_digit(16) {
-                    Some(d) => d as usize,
-                    None => panic!("Bork. Bork.")
+    let progress = animate::Progress::new(password_len);
+    let start = Instant::now();
+    let mut last_index = 0;
+    let mut cached = cached.into_iter();
+    // Only started once the cached hits run out.
+    let mut hits: Option<search::Hits> = None;
+    loop {
+        let hit = match cached.next() {
+            Some(hit) => hit,
+            None => {
+                let hits = hits.get_or_insert_with(|| search::hits(&options.door_id, zeros, options.threads, resume_at));
+                let hit = match hits.next() {
+                    Some(hit) => hit,
+                    None => break,
                 };
+                if let Some(ref mut c) = cache {
+                    if let Err(e) = c.add(&hit).and_then(|_| c.set_searched(hits.searched())) {
+                        eprintln!("Not caching any more hashes: {}", e);
+                        cache = None;
+                    }
+                }
+                hit
+            }
+        };
+        let digest = hit.digest;
+        last_index = hit.index;
+        // First char after the zeroes used to find both password:
+        // - Char in first password
+        // - Position of char in second password
+        let c = match digest.chars().nth(zeros) {
+            Some(c) => c,
+            None => panic!("Bork. Bork."),
+        };
 
-                if pos < PASSWORD_LEN && password_two[pos] == ' ' {
-                    // Sixt char is a char in the second password
-                    match digest.chars().nth(6) {
-                        Some(c) => {
-                            password_two[pos] = c;
-                            password_two_valid_chars += 1;
-                            password_two_found = password_two_valid_chars == PASSWORD_LEN;
+        if !password_one_found {
+            password_one[password_one_pos] = c;
+            password_one_pos += 1;
+            password_one_found = password_one_pos == password_len;
+        }
+        if !password_two_found {
+            let pos = match c.to_digit(16) {
+                Some(d) => d as usize,
+                None => panic!("Bork. Bork."),
+            };
 
-                        },
-                        None => panic!("Bork. Bork.")
-                    };
-                }
+            if pos < password_len && password_two[pos] == ' ' {
+                // Next char is a char in the second password
+                match digest.chars().nth(zeros + 1) {
+                    Some(c) => {
+                        password_two[pos] = c;
+                        password_two_valid_chars += 1;
+                        password_two_found = password_two_valid_chars == password_len;
+                    }
+                    None => panic!("Bork. Bork."),
+                };
             }
         }
-        count += 1;
-        h.reset();
+        progress.update(hit.index, &password_one, &password_two);
+        if password_one_found && password_two_found {
+            break;
+        }
+    }
+    progress.finish(&password_one, &password_two);
+    match hits {
+        Some(_) => eprintln!(
+            "Searched {} indices with {} thread(s) in {:.2}s, {} of them cached",
+            last_index + 1,
+            options.threads,
+            start.elapsed().as_secs_f64(),
+            resume_at.min(last_index + 1)
+        ),
+        None => eprintln!("Found everything in the cache, {} indices searched before", last_index + 1),
     }
-    let password_one: String = password_one.into_iter().collect();
-    let password_two: String = password_two.into_iter().collect();
-    println!("\npassword one: {}\npassword two: {}", password_one, password_two);
 }
@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use hash::{self, Backend};
+
+// Indices a thread hashes before it checks for more work.
+pub const CHUNK_SIZE: u64 = 20_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub index: u64,
+    // The hash in hex.
+    pub digest: String,
+}
+
+// Writes `n` in decimal to the end of `buf`, returning the digits.
+fn format_index(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buf[i..];
+        }
+    }
+}
+
+fn has_password_char(digest: &[u8; 16], zeros: usize) -> bool {
+    digest[..zeros / 2].iter().all(|&b| b == 0) && (zeros.is_multiple_of(2) || digest[zeros / 2] >> 4 == 0)
+}
+
+// Hashes the `CHUNK_SIZE` indices from `start` on. The door ID is hashed once, every index
+// starts from a copy of that.
+pub fn search_chunk<B: Backend>(door_id: &str, zeros: usize, start: u64) -> Vec<Hit> {
+    let mut prefix = B::new();
+    prefix.update(door_id.as_bytes());
+    let mut buf = [0; 20];
+    let mut hits = Vec::new();
+    for index in start..start + CHUNK_SIZE {
+        let mut h = prefix.clone();
+        h.update(format_index(index, &mut buf));
+        let digest = h.finish();
+        if has_password_char(&digest, zeros) {
+            hits.push(Hit { index, digest: hash::hex(&digest) });
+        }
+    }
+    hits
+}
+
+// Hashes that start with enough zeroes, in index order. The indices are handed out to the threads
+// a chunk at a time, chunks that finish early wait until all chunks before them are in.
+pub struct Hits {
+    stop: Arc<AtomicBool>,
+    receiver: Option<Receiver<(u64, Vec<Hit>)>>,
+    workers: Vec<JoinHandle<()>>,
+    pending: BTreeMap<u64, Vec<Hit>>,
+    next_chunk: u64,
+    ready: VecDeque<Hit>,
+    start: u64,
+}
+
+impl Hits {
+    // Every index below this one has been searched and its hit, if any, returned.
+    pub fn searched(&self) -> u64 {
+        match self.ready.front() {
+            Some(hit) => hit.index,
+            None => self.start + self.next_chunk * CHUNK_SIZE,
+        }
+    }
+}
+
+impl Iterator for Hits {
+    type Item = Hit;
+
+    fn next(&mut self) -> Option<Hit> {
+        loop {
+            if let Some(hit) = self.ready.pop_front() {
+                return Some(hit);
+            }
+            if let Some(hits) = self.pending.remove(&self.next_chunk) {
+                self.ready.extend(hits);
+                self.next_chunk += 1;
+                continue;
+            }
+            let (chunk, hits) = self.receiver.as_ref()?.recv().ok()?;
+            self.pending.insert(chunk, hits);
+        }
+    }
+}
+
+impl Drop for Hits {
+    fn drop(&mut self) {
+        // Threads stop after their current chunk, or as soon as they can't send it.
+        self.stop.store(true, Ordering::Relaxed);
+        self.receiver = None;
+        for worker in self.workers.drain(..) {
+            worker.join().expect("Search thread panicked.");
+        }
+    }
+}
+
+// Searches for hashes starting with `zeros` zeroes with `threads` threads from index `start` on,
+// until the returned iterator is dropped.
+pub fn hits(door_id: &str, zeros: usize, threads: usize, start: u64) -> Hits {
+    hits_between(door_id, zeros, threads, start, u64::MAX)
+}
+
+// Like `hits`, but the search ends at the first chunk that starts at `end` or later.
+pub fn hits_between(door_id: &str, zeros: usize, threads: usize, start: u64, end: u64) -> Hits {
+    let stop = Arc::new(AtomicBool::new(false));
+    let chunks = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let workers = (0..threads.max(1))
+        .map(|_| {
+            let (stop, chunks, sender) = (stop.clone(), chunks.clone(), sender.clone());
+            let door_id = door_id.to_string();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let chunk = chunks.fetch_add(1, Ordering::Relaxed);
+                    let from = start.saturating_add(chunk.saturating_mul(CHUNK_SIZE));
+                    if from >= end || sender.send((chunk, search_chunk::<hash::Md5>(&door_id, zeros, from))).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+    Hits {
+        stop,
+        receiver: Some(receiver),
+        workers,
+        pending: BTreeMap::new(),
+        next_chunk: 0,
+        ready: VecDeque::new(),
+        start,
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use op::Op;
+use screen::Screen;
+
+const GREEN: &str = "\x1b[1;32m";
+const RED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+const HOME: &str = "\x1b[H";
+const CLEAR: &str = "\x1b[2J";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    // Colours pixels that turned on green and pixels that turned off red.
+    Terminal,
+    // Marks pixels that turned on with '+' and pixels that turned off with '-'.
+    Text,
+}
+
+// Draws `screen`, marking the pixels that differ from `previous`. Returns the drawing along with
+// how many pixels turned on and off.
+fn frame(previous: &Screen, screen: &Screen, style: Style) -> (String, usize, usize) {
+    let (mut on, mut off) = (0, 0);
+    let mut drawing = String::new();
+    for y in 0..screen.height() {
+        for x in 0..screen.width() {
+            let (b, a) = (previous.get(x, y), screen.get(x, y));
+            match (b, a, style) {
+                (false, true, Style::Terminal) => drawing.push_str(&format!("{}#{}", GREEN, RESET)),
+                (true, false, Style::Terminal) => drawing.push_str(&format!("{}.{}", RED, RESET)),
+                (_, true, Style::Terminal) => drawing.push('#'),
+                (_, _, Style::Terminal) => drawing.push(' '),
+                (false, true, Style::Text) => drawing.push('+'),
+                (true, false, Style::Text) => drawing.push('-'),
+                (_, true, Style::Text) => drawing.push('#'),
+                (_, _, Style::Text) => drawing.push('.'),
+            }
+            match (b, a) {
+                (false, true) => on += 1,
+                (true, false) => off += 1,
+                _ => (),
+            }
+        }
+        drawing.push('\n');
+    }
+    (drawing, on, off)
+}
+
+// Calls `f` with the heading and drawing of every frame, starting with the blank screen.
+fn frames<F>(ops: &[Op], width: usize, height: usize, style: Style, mut f: F) -> io::Result<()>
+where
+    F: FnMut(&str, &str) -> io::Result<()>,
+{
+    let mut screen = Screen::new(width, height);
+    let (drawing, _, _) = frame(&screen, &screen, style);
+    f(&format!("step 0/{}: blank screen", ops.len()), &drawing)?;
+    for (i, &op) in ops.iter().enumerate() {
+        let previous = screen.clone();
+        screen.apply(op);
+        let (drawing, on, off) = frame(&previous, &screen, style);
+        let heading = format!(
+            "step {}/{}: {} (+{} -{}, {} lit)",
+            i + 1,
+            ops.len(),
+            op,
+            on,
+            off,
+            screen.lit_pixels()
+        );
+        f(&heading, &drawing)?;
+    }
+    Ok(())
+}
+
+// Redraws the screen in place in the terminal after every operation, waiting `delay` in between.
+pub fn play(ops: &[Op], width: usize, height: usize, delay: Duration) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    write!(stdout, "{}", CLEAR)?;
+    frames(ops, width, height, Style::Terminal, |heading, drawing| {
+        // Clearing the line keeps a shorter heading from leaving bits of the previous one behind.
+        write!(stdout, "{}{}\x1b[K\n{}", HOME, heading, drawing)?;
+        stdout.flush()?;
+        thread::sleep(delay);
+        Ok(())
+    })
+}
+
+// Writes every frame one after the other, separated by blank lines.
+pub fn dump<W: Write>(ops: &[Op], width: usize, height: usize, writer: &mut W) -> io::Result<()> {
+    frames(ops, width, height, Style::Text, |heading, drawing| {
+        writeln!(writer, "{}\n{}", heading, drawing)
+    })?;
+    writer.flush()
+}
@@ -0,0 +1,60 @@
+use std::io::{self, Write};
+
+use op::Op;
+use screen::Screen;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // line,op,lit,on,off with the pixels as space separated x:y pairs.
+    Csv,
+    // One object per line, with the pixels as [x, y] pairs.
+    Json,
+}
+
+fn csv_pixels(pixels: &[(usize, usize)]) -> String {
+    let pixels: Vec<String> = pixels.iter().map(|&(x, y)| format!("{}:{}", x, y)).collect();
+    pixels.join(" ")
+}
+
+fn json_pixels(pixels: &[(usize, usize)]) -> String {
+    let pixels: Vec<String> = pixels.iter().map(|&(x, y)| format!("[{},{}]", x, y)).collect();
+    format!("[{}]", pixels.join(","))
+}
+
+// Writes a record for every operation with its line in the input, the number of lit pixels after
+// it and the pixels it turned on and off.
+pub fn write<W: Write>(ops: &[(usize, Op)], width: usize, height: usize, format: Format, writer: &mut W) -> io::Result<()> {
+    if format == Format::Csv {
+        writeln!(writer, "line,op,lit,on,off")?;
+    }
+    let mut screen = Screen::new(width, height);
+    for &(line, op) in ops {
+        let before = screen.clone();
+        screen.apply(op);
+        let changes = screen.changes(&before);
+        let on: Vec<(usize, usize)> = changes.iter().filter(|c| c.2).map(|c| (c.0, c.1)).collect();
+        let off: Vec<(usize, usize)> = changes.iter().filter(|c| !c.2).map(|c| (c.0, c.1)).collect();
+        // Operations never contain quotes or backslashes, so they don't need escaping.
+        match format {
+            Format::Csv => writeln!(
+                writer,
+                "{},\"{}\",{},{},{}",
+                line,
+                op,
+                screen.lit_pixels(),
+                csv_pixels(&on),
+                csv_pixels(&off)
+            )?,
+            Format::Json => writeln!(
+                writer,
+                "{{\"line\":{},\"op\":\"{}\",\"lit\":{},\"on\":{},\"off\":{}}}",
+                line,
+                op,
+                screen.lit_pixels(),
+                json_pixels(&on),
+                json_pixels(&off)
+            )?,
+        }
+    }
+    writer.flush()
+}
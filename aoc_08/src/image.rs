@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+
+use gif;
+#[cfg(feature = "png")]
+use png;
+
+use op::Op;
+use screen::Screen;
+
+// Lit pixels are drawn black on white, like the '#' of `Screen::draw`.
+const PALETTE: [u8; 6] = [0xff, 0xff, 0xff, 0x00, 0x00, 0x00];
+
+// The screen with every pixel blown up to `scale` by `scale` pixels, one byte per pixel, row by
+// row. Lit pixels are 1.
+fn scaled(screen: &Screen, scale: usize) -> Vec<u8> {
+    let mut pixels = Vec::new();
+    for y in 0..screen.height() {
+        let line: Vec<u8> = (0..screen.width()).flat_map(|x| vec![screen.get(x, y) as u8; scale]).collect();
+        for _ in 0..scale {
+            pixels.extend_from_slice(&line);
+        }
+    }
+    pixels
+}
+
+// Images are held in memory a byte per pixel, so large scales fail instead of allocating this
+// much or more.
+const MAX_PIXELS: usize = 1 << 28;
+
+// The size of the screen blown up to `scale`, as long as neither side is longer than `max_side`
+// and the image isn't larger than MAX_PIXELS.
+fn size(screen: &Screen, scale: usize, max_side: usize) -> io::Result<(usize, usize)> {
+    let width = screen.width().checked_mul(scale).filter(|&w| w <= max_side);
+    let height = screen.height().checked_mul(scale).filter(|&h| h <= max_side);
+    match (width, height) {
+        (Some(w), Some(h)) if w.checked_mul(h).is_some_and(|pixels| pixels <= MAX_PIXELS) => Ok((w, h)),
+        _ => {
+            let error = format!("image of a {}x{} screen at scale {} is too large", screen.width(), screen.height(), scale);
+            Err(io::Error::new(io::ErrorKind::InvalidInput, error))
+        }
+    }
+}
+
+// Writes the screen as a binary PBM, where 1 is black.
+pub fn write_pbm<W: Write>(screen: &Screen, scale: usize, writer: &mut W) -> io::Result<()> {
+    let (width, height) = size(screen, scale, usize::MAX)?;
+    write!(writer, "P4\n{} {}\n", width, height)?;
+    for line in scaled(screen, scale).chunks(width.max(1)) {
+        // Every row starts on a new byte, most significant bit first.
+        let packed: Vec<u8> = line
+            .chunks(8)
+            .map(|bits| bits.iter().enumerate().fold(0, |byte, (i, &b)| byte | b << (7 - i)))
+            .collect();
+        writer.write_all(&packed)?;
+    }
+    writer.flush()
+}
+
+#[cfg(feature = "png")]
+pub fn write_png<W: Write>(screen: &Screen, scale: usize, writer: &mut W) -> io::Result<()> {
+    let (width, height) = size(screen, scale, u32::MAX as usize)?;
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels: Vec<u8> = scaled(screen, scale).iter().map(|&p| if p == 1 { 0x00 } else { 0xff }).collect();
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+// Writes an animated GIF that starts with the blank screen and shows the screen after every
+// operation, `delay` hundredths of a second apart.
+pub fn write_gif<W: Write>(ops: &[Op], width: usize, height: usize, scale: usize, delay: u16, writer: W) -> io::Result<()> {
+    let mut screen = Screen::new(width, height);
+    let (w, h) = size(&screen, scale, u16::MAX as usize)?;
+
+    let mut encoder = gif::Encoder::new(writer, w as u16, h as u16, &PALETTE).map_err(io::Error::other)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+    let mut write_frame = |screen: &Screen| {
+        let mut frame = gif::Frame::from_indexed_pixels(w as u16, h as u16, scaled(screen, scale), None);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(io::Error::other)
+    };
+    write_frame(&screen)?;
+    for &op in ops {
+        screen.apply(op);
+        write_frame(&screen)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_pbm() {
+        let screen = Screen::parse("#.#\n.#", 3, 2).unwrap();
+        let mut pbm = Vec::new();
+        write_pbm(&screen, 1, &mut pbm).unwrap();
+        assert_eq!(pbm, b"P4\n3 2\n\xa0\x40");
+        pbm.clear();
+        write_pbm(&screen, 3, &mut pbm).unwrap();
+        assert_eq!(&pbm[..7], b"P4\n9 6\n");
+        assert_eq!(&pbm[7..], b"\xe3\x80\xe3\x80\xe3\x80\x1c\x00\x1c\x00\x1c\x00");
+    }
+
+    #[test]
+    fn large_scales_fail_before_allocating() {
+        let screen = Screen::new(50, 6);
+        for &scale in &[100_000, usize::MAX / 10, usize::MAX] {
+            let mut pbm = Vec::new();
+            let e = write_pbm(&screen, scale, &mut pbm).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+            assert!(pbm.is_empty());
+            let e = write_gif(&[], 50, 6, scale, 10, Vec::new()).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(size(&screen, 100, usize::MAX).unwrap(), (5_000, 600));
+        assert!(size(&screen, 1000, usize::MAX).is_err());
+        // 50 times 1310 is the widest a GIF can be.
+        assert_eq!(size(&Screen::new(50, 1), 1310, u16::MAX as usize).unwrap(), (65_500, 1310));
+        assert!(size(&Screen::new(50, 1), 1311, u16::MAX as usize).is_err());
+        assert!(size(&Screen::new(70_000, 1), 1, u16::MAX as usize).is_err());
+        assert!(size(&Screen::new(70_000, 1), 1, u32::MAX as usize).is_ok());
+    }
+}
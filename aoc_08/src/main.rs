@@ -56,92 +56,353 @@
 // There seems to be an intermediate check of the voltage used by the display: after you swipe your
 // card, if the screen did work, how many pixels should be lit?
 
+extern crate gif;
+#[cfg(feature = "png")]
+extern crate png;
+
+mod animate;
+mod history;
+mod image;
+mod ocr;
+mod op;
+mod screen;
+mod solve;
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{self, BufWriter};
 use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use op::Op;
+use screen::Screen;
+
+// Size of the puzzle's screen, used unless the input or the command line says otherwise.
+const WIDTH: usize = 50;
+const HEIGHT: usize = 6;
+// Time between frames of `play`, in milliseconds.
+const DELAY: u64 = 100;
+
+fn usage(prog_name: &str) {
+    eprintln!("{} [--size WxH] [INPUT]", prog_name);
+    eprintln!("{} play [--size WxH] [--delay MS] [INPUT]", prog_name);
+    eprintln!("{} frames [--size WxH] INPUT OUTPUT", prog_name);
+    eprintln!("{} export [--size WxH] [--scale N] [--delay MS] INPUT OUTPUT", prog_name);
+    eprintln!("{} explain [--size WxH] INPUT [SCREEN]", prog_name);
+    eprintln!("{} synth [--size WxH] IMAGE", prog_name);
+    eprintln!("{} history [--size WxH] [--format csv|json] [INPUT]", prog_name);
+    eprintln!();
+    eprintln!("Reads `input` unless INPUT is given. The screen is {}x{} unless the input starts", WIDTH, HEIGHT);
+    eprintln!("with a `screen WxH` line, --size overrides both. Screens 6 pixels tall are read as");
+    eprintln!("text in the puzzle's 5x6 font.");
+    eprintln!();
+    eprintln!("play redraws the screen after every operation, {}ms apart unless --delay says", DELAY);
+    eprintln!("otherwise, with the pixels that changed highlighted. frames writes the same frames");
+    eprintln!("to OUTPUT as text, with '+' and '-' for pixels that turned on and off.");
+    eprintln!();
+    eprintln!("export writes the final screen to OUTPUT.pbm or OUTPUT.png, or every frame to an");
+    eprintln!("animated OUTPUT.gif, with every pixel drawn N by N. PNG needs the `png` feature.");
+    eprintln!();
+    eprintln!("explain works back from the final screen to show which pixels of every rect are");
+    eprintln!("still lit at the end because of it. Given a SCREEN drawn with '#' for lit pixels,");
+    eprintln!("it is used as the final screen and pixels the operations can't explain are listed.");
+    eprintln!("synth prints operations that draw IMAGE, drawn the same way, on a blank screen the");
+    eprintln!("size of the image.");
+    eprintln!();
+    eprintln!("history prints the line, lit pixel count and the pixels turned on and off of every");
+    eprintln!("operation, as CSV unless --format says json.");
+    eprintln!();
+    eprintln!("Operations:");
+    for &(form, help) in &op::HELP {
+        eprintln!("    {:<26} {}", form, help);
+    }
+}
 
-fn draw_rect(screen: &mut [Vec<u32>], w: usize, h: usize) {
-    for y in 0..h {
-        for x in 0..w {
-            screen[y][x] = 1;
+fn fail(name: &str, e: &dyn fmt::Display) -> ! {
+    eprintln!("{}: {}", name, e);
+    process::exit(1);
+}
+
+// Splits `args` into the values of `flags`, which all take one, and the remaining arguments.
+// Returns None on flags that aren't in `flags`.
+fn parse_args<'a>(args: &'a [String], flags: &[&str]) -> Option<(HashMap<&'a str, &'a str>, Vec<&'a str>)> {
+    let mut values = HashMap::new();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if flags.contains(&arg.as_str()) {
+            values.insert(arg.as_str(), args.next()?.as_str());
+        } else if arg.starts_with("--") {
+            return None;
+        } else {
+            rest.push(arg.as_str());
         }
     }
+    Some((values, rest))
+}
+
+// Reads and parses `file_name`, returning the screen size along with the operations and their
+// line numbers.
+fn load_lines(file_name: &str, size: Option<&str>) -> (usize, usize, Vec<(usize, Op)>) {
+    let size = size.map(|s| op::parse_size(s).unwrap_or_else(|e| fail("--size", &e)));
+    let mut input = String::new();
+    let mut file = File::open(Path::new(file_name)).unwrap_or_else(|e| fail(file_name, &e));
+    file.read_to_string(&mut input).unwrap_or_else(|e| fail(file_name, &e));
+    let header = op::screen_size(&input).unwrap_or_else(|e| fail(file_name, &e));
+    let (width, height) = size.or(header).unwrap_or((WIDTH, HEIGHT));
+    let ops = op::parse(&input, width, height).unwrap_or_else(|e| fail(file_name, &e));
+    (width, height, ops)
 }
 
-fn rotate(row: &[u32], offset: usize) -> Vec<u32> {
-    // if offset == 0 {
-    //     return row.to_vec();
-    // }
-    let mut rotated = vec![0; row.len()];
+fn load(file_name: &str, size: Option<&str>) -> (usize, usize, Vec<Op>) {
+    let (width, height, ops) = load_lines(file_name, size);
+    (width, height, ops.into_iter().map(|(_, op)| op).collect())
+}
 
-    for (i, v) in row.iter().enumerate() {
-        rotated[(i + offset) % row.len()] = *v;
+// [--size WxH] [INPUT]
+fn run(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.len() > 1 {
+        return false;
+    }
+    let file_name = paths.first().cloned().unwrap_or("input");
+    let (width, height, ops) = load(file_name, flags.get("--size").cloned());
+
+    let mut screen = Screen::new(width, height);
+    for op in ops {
+        screen.apply(op);
+    }
+
+    println!("Lit pixels: {}", screen.lit_pixels());
+    screen.draw();
+
+    // Only screens one letter tall are read, like the puzzle's. Not every such screen shows text,
+    // so one that can't be read is only worth a warning.
+    match ocr::read(&screen) {
+        Some(Ok(text)) => println!("Code: {}", text),
+        Some(Err(e)) => eprintln!("{}: warning: {}", file_name, e),
+        None => (),
+    }
+    true
+}
+
+// play [--size WxH] [--delay MS] [INPUT]
+fn run_play(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size", "--delay"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.len() > 1 {
+        return false;
+    }
+    let delay = match flags.get("--delay").map(|d| d.parse()) {
+        Some(Ok(delay)) => delay,
+        Some(Err(_)) => return false,
+        None => DELAY,
+    };
+    let file_name = paths.first().cloned().unwrap_or("input");
+    let (width, height, ops) = load(file_name, flags.get("--size").cloned());
+    if let Err(e) = animate::play(&ops, width, height, Duration::from_millis(delay)) {
+        fail("stdout", &e);
+    }
+    true
+}
+
+// frames [--size WxH] INPUT OUTPUT
+fn run_frames(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.len() != 2 {
+        return false;
+    }
+    let (width, height, ops) = load(paths[0], flags.get("--size").cloned());
+    let written = File::create(Path::new(paths[1]))
+        .and_then(|file| animate::dump(&ops, width, height, &mut BufWriter::new(file)));
+    if let Err(e) = written {
+        fail(paths[1], &e);
     }
-    rotated
+    true
 }
 
-fn get_column(screen: &[Vec<u32>], col: usize) -> Vec<u32> {
-    let len = screen.len();
-    let mut column = vec![0; len];
+// export [--size WxH] [--scale N] [--delay MS] INPUT OUTPUT
+fn run_export(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size", "--scale", "--delay"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.len() != 2 {
+        return false;
+    }
+    let scale = match flags.get("--scale").map(|s| s.parse()) {
+        Some(Ok(scale)) if scale > 0 => scale,
+        Some(_) => return false,
+        None => 1,
+    };
+    // GIFs count in hundredths of a second.
+    let delay = match flags.get("--delay").map(|d| d.parse::<u64>()) {
+        Some(Ok(delay)) => (delay / 10).min(u16::MAX as u64) as u16,
+        Some(Err(_)) => return false,
+        None => (DELAY / 10) as u16,
+    };
+    let (width, height, ops) = load(paths[0], flags.get("--size").cloned());
+    let output = Path::new(paths[1]);
 
-    for i in 0..len {
-        column[i] = screen[i][col];
+    let mut screen = Screen::new(width, height);
+    for &op in &ops {
+        screen.apply(op);
+    }
+    let written = match output.extension().and_then(|e| e.to_str()) {
+        Some("pbm") => File::create(output).and_then(|f| image::write_pbm(&screen, scale, &mut BufWriter::new(f))),
+        #[cfg(feature = "png")]
+        Some("png") => File::create(output).and_then(|f| image::write_png(&screen, scale, &mut BufWriter::new(f))),
+        #[cfg(not(feature = "png"))]
+        Some("png") => fail(paths[1], &"built without the `png` feature"),
+        Some("gif") => File::create(output)
+            .and_then(|f| image::write_gif(&ops, width, height, scale, delay, BufWriter::new(f))),
+        _ => fail(paths[1], &"unknown image format, use .pbm, .png or .gif"),
+    };
+    if let Err(e) = written {
+        fail(paths[1], &e);
     }
+    true
+}
 
-    column
+// Reads a drawing with '#' for lit pixels.
+fn read_drawing(file_name: &str) -> String {
+    let mut drawing = String::new();
+    let mut file = File::open(Path::new(file_name)).unwrap_or_else(|e| fail(file_name, &e));
+    file.read_to_string(&mut drawing).unwrap_or_else(|e| fail(file_name, &e));
+    drawing
 }
 
-fn draw_screen(screen: &[Vec<u32>]) {
-    for row in screen {
-        for pixel in row {
-            print!("{}", if pixel == &1 { '#' } else { ' ' });
+fn parse_drawing(file_name: &str, drawing: &str, width: usize, height: usize) -> Screen {
+    Screen::parse(drawing, width, height)
+        .unwrap_or_else(|| fail(file_name, &format!("drawing doesn't fit on a {}x{} screen", width, height)))
+}
+
+// explain [--size WxH] INPUT [SCREEN]
+fn run_explain(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.is_empty() || paths.len() > 2 {
+        return false;
+    }
+    let (width, height, ops) = load(paths[0], flags.get("--size").cloned());
+    let screen = match paths.get(1) {
+        Some(file_name) => parse_drawing(file_name, &read_drawing(file_name), width, height),
+        None => {
+            let mut screen = Screen::new(width, height);
+            for &op in &ops {
+                screen.apply(op);
+            }
+            screen
         }
-        println!("");
+    };
+
+    let (rects, conflicts) = solve::explain(&ops, &screen);
+    for rect in rects {
+        let lit = rect.lit.iter().filter(|&&l| l).count();
+        println!("step {}: {} ({} of {} pixels stay lit)", rect.step, ops[rect.step - 1], lit, rect.lit.len());
+        for row in rect.lit.chunks(rect.w.max(1)) {
+            println!("{}", row.iter().map(|&l| if l { '#' } else { '.' }).collect::<String>());
+        }
+    }
+    for conflict in &conflicts {
+        eprintln!("{}: {}", paths.get(1).unwrap_or(&paths[0]), conflict);
     }
+    if !conflicts.is_empty() {
+        process::exit(1);
+    }
+    true
 }
 
-fn main() {
-    // 6 x 50
-    let mut screen = vec![
-    vec![0; 50]; 6];
-
-    let path = Path::new("input");
-    let file = BufReader::new(File::open(&path).expect("Couldn't open file."));
-
-    for line in file.lines() {
-        match line {
-            Ok(l) => {
-                // There are two numbers per line. Possible cases:
-                // - rotate column x=27 by 1 => indices: 4, 6
-                // - rotate row y=2 by 35 => indicies: 3, 5
-                // - rect 9x1 => indicies: 1, 2
-                let tokens: Vec<&str> = l.split(|c| c == ' ' || c == '=' || c == 'x').collect();
-                if l.starts_with("rotate row") {
-                    let row: usize = tokens[3].parse().unwrap();
-                    let offset: usize = tokens[5].parse().unwrap();
-                    screen[row] = rotate(&screen[row], offset);
-                } else if l.starts_with("rotate column") {
-                    let col: usize = tokens[4].parse().unwrap();
-                    let offset: usize = tokens[6].parse().unwrap();
-                    let column = rotate(&get_column(&screen, col), offset);
-                    for (i, v) in column.iter().enumerate() {
-                        screen[i][col] = *v;
-                    }
-                } else if l.starts_with("rect") {
-                    let w: usize = tokens[1].parse().unwrap();
-                    let h: usize = tokens[2].parse().unwrap();
-                    draw_rect(&mut screen, w, h);
-                }
-            }
-            Err(_) => panic!("Bork. Bork."),
+// synth [--size WxH] IMAGE
+fn run_synth(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.len() != 1 {
+        return false;
+    }
+    let drawing = read_drawing(paths[0]);
+    let size = match flags.get("--size") {
+        Some(size) => op::parse_size(size).unwrap_or_else(|e| fail("--size", &e)),
+        None => {
+            let lines: Vec<&str> = drawing.lines().collect();
+            let width = lines.iter().map(|l| l.trim_end().chars().count()).max().unwrap_or(0);
+            let height = lines.iter().rposition(|l| !l.trim().is_empty()).map_or(0, |y| y + 1);
+            (width, height)
         }
+    };
+    if size.0 == 0 || size.1 == 0 {
+        fail(paths[0], &"empty image");
+    }
+    let image = parse_drawing(paths[0], &drawing, size.0, size.1);
+
+    let ops = solve::synthesize(&image);
+    let mut screen = Screen::new(size.0, size.1);
+    for &op in &ops {
+        screen.apply(op);
+    }
+    if screen != image {
+        fail(paths[0], &"the synthesized operations don't draw the image");
+    }
+
+    println!("screen {}x{}", size.0, size.1);
+    for op in ops {
+        println!("{}", op);
     }
+    true
+}
+
+// history [--size WxH] [--format csv|json] [INPUT]
+fn run_history(args: &[String]) -> bool {
+    let (flags, paths) = match parse_args(args, &["--size", "--format"]) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    if paths.len() > 1 {
+        return false;
+    }
+    let format = match flags.get("--format").cloned() {
+        None | Some("csv") => history::Format::Csv,
+        Some("json") => history::Format::Json,
+        Some(_) => return false,
+    };
+    let file_name = paths.first().cloned().unwrap_or("input");
+    let (width, height, ops) = load_lines(file_name, flags.get("--size").cloned());
+    let stdout = io::stdout();
+    if let Err(e) = history::write(&ops, width, height, format, &mut BufWriter::new(stdout.lock())) {
+        fail("stdout", &e);
+    }
+    true
+}
 
-    let mut sum_lit_pixels = 0;
-    for v in &screen {
-        sum_lit_pixels += v.iter().fold(0, |sum, p| sum + p);
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let ok = match args.get(1).map(|a| a.as_str()) {
+        Some("play") => run_play(&args[2..]),
+        Some("frames") => run_frames(&args[2..]),
+        Some("export") => run_export(&args[2..]),
+        Some("explain") => run_explain(&args[2..]),
+        Some("synth") => run_synth(&args[2..]),
+        Some("history") => run_history(&args[2..]),
+        _ => run(&args[1..]),
+    };
+    if !ok {
+        usage(&args[0]);
+        process::exit(2);
     }
-    println!("Lit pixels: {}", sum_lit_pixels);
-    draw_screen(&screen);
 }
@@ -0,0 +1,159 @@
+use std::error;
+use std::fmt;
+
+use screen::Screen;
+
+// Letters are drawn in cells of 5x6 pixels, most of them leave the rightmost column blank.
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 6;
+
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 19] = [
+    ('A', [".##..", "#..#.", "#..#.", "####.", "#..#.", "#..#."]),
+    ('B', ["###..", "#..#.", "###..", "#..#.", "#..#.", "###.."]),
+    ('C', [".##..", "#..#.", "#....", "#....", "#..#.", ".##.."]),
+    ('E', ["####.", "#....", "###..", "#....", "#....", "####."]),
+    ('F', ["####.", "#....", "###..", "#....", "#....", "#...."]),
+    ('G', [".##..", "#..#.", "#....", "#.##.", "#..#.", ".###."]),
+    ('H', ["#..#.", "#..#.", "####.", "#..#.", "#..#.", "#..#."]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..##.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#..#.", "#.#..", "##...", "#.#..", "#.#..", "#..#."]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "####."]),
+    ('O', [".##..", "#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    ('P', ["###..", "#..#.", "#..#.", "###..", "#....", "#...."]),
+    ('R', ["###..", "#..#.", "#..#.", "###..", "#.#..", "#..#."]),
+    ('S', [".###.", "#....", "#....", ".##..", "...#.", "###.."]),
+    ('U', ["#..#.", "#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####.", "...#.", "..#..", ".#...", "#....", "####."]),
+    (' ', [".....", ".....", ".....", ".....", ".....", "....."]),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    // The text with a '?' for every cell that couldn't be read.
+    pub text: String,
+    // Index of every unknown cell along with its pixels, drawn like the font.
+    pub unknown: Vec<(usize, Vec<String>)>,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "couldn't read \"{}\"", self.text)?;
+        for &(i, ref glyph) in &self.unknown {
+            write!(f, "\nletter {} (columns {}..{}) isn't in the font:", i + 1, i * GLYPH_WIDTH, (i + 1) * GLYPH_WIDTH)?;
+            for row in glyph {
+                write!(f, "\n    {}", row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
+
+// The pixels of the cell at `index`, columns past the edge of the screen count as off.
+fn glyph(screen: &Screen, index: usize) -> Vec<String> {
+    (0..screen.height())
+        .map(|y| {
+            (index * GLYPH_WIDTH..(index + 1) * GLYPH_WIDTH)
+                .map(|x| if x < screen.width() && screen.get(x, y) { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+// Reads the letters off a screen that is exactly one letter tall, or returns None if it isn't.
+pub fn read(screen: &Screen) -> Option<Result<String, Error>> {
+    if screen.height() != GLYPH_HEIGHT {
+        return None;
+    }
+    let width = screen.width();
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    for i in 0..width.div_ceil(GLYPH_WIDTH) {
+        let glyph = glyph(screen, i);
+        match FONT.iter().find(|&(_, rows)| rows[..] == glyph[..]) {
+            Some(&(c, _)) => text.push(c),
+            None => {
+                text.push('?');
+                unknown.push((i, glyph));
+            }
+        }
+    }
+    let text = text.trim_end().to_string();
+    if unknown.is_empty() {
+        Some(Ok(text))
+    } else {
+        Some(Err(Error { text, unknown }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use op;
+
+    // Draws `text` with the font, one cell per character.
+    fn render(text: &str, width: usize) -> Screen {
+        let mut screen = Screen::new(width, GLYPH_HEIGHT);
+        for (i, c) in text.chars().enumerate() {
+            let &(_, rows) = FONT.iter().find(|&&(f, _)| f == c).expect("character not in the font");
+            for (y, row) in rows.iter().enumerate() {
+                for (x, pixel) in row.chars().enumerate() {
+                    if pixel == '#' && i * GLYPH_WIDTH + x < width {
+                        screen.set(i * GLYPH_WIDTH + x, y, true);
+                    }
+                }
+            }
+        }
+        screen
+    }
+
+    #[test]
+    fn reads_rendered_text() {
+        for &text in &["EOARGPHYAO", "ABCEFGHIJKLOPRSUYZ", "L", "HI JO"] {
+            assert_eq!(read(&render(text, text.len() * GLYPH_WIDTH)), Some(Ok(text.to_string())));
+        }
+    }
+
+    #[test]
+    fn trailing_blank_cells_and_cut_off_columns() {
+        // 50 wide like the puzzle's, the last cell blank.
+        assert_eq!(read(&render("EOARGPHYA", 50)), Some(Ok("EOARGPHYA".to_string())));
+        // Most letters leave their last column blank, so a screen one short still reads.
+        assert_eq!(read(&render("ZU", 9)), Some(Ok("ZU".to_string())));
+    }
+
+    #[test]
+    fn reads_the_puzzle_input() {
+        let ops = op::parse(include_str!("../input"), 50, 6).unwrap();
+        let mut screen = Screen::new(50, 6);
+        for (_, op) in ops {
+            screen.apply(op);
+        }
+        assert_eq!(read(&screen), Some(Ok("RURUCEOEIL".to_string())));
+    }
+
+    #[test]
+    fn unknown_glyphs() {
+        let mut screen = render("EO A", 20);
+        screen.set(11, 2, true);
+        screen.set(17, 0, false);
+        let expected = Error {
+            text: "EO??".to_string(),
+            unknown: vec![
+                (2, vec![".....", ".....", ".#...", ".....", ".....", "....."].into_iter().map(String::from).collect()),
+                (3, vec![".#...", "#..#.", "#..#.", "####.", "#..#.", "#..#."].into_iter().map(String::from).collect()),
+            ],
+        };
+        assert_eq!(read(&screen), Some(Err(expected)));
+    }
+
+    #[test]
+    fn only_screens_one_letter_tall() {
+        assert_eq!(read(&Screen::new(50, 7)), None);
+        assert_eq!(read(&Screen::new(50, 5)), None);
+        assert_eq!(read(&Screen::new(50, 6)), Some(Ok(String::new())));
+    }
+}
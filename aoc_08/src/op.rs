@@ -0,0 +1,383 @@
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    // rect AxB
+    Rect { w: usize, h: usize },
+    // rotate row y=A by B
+    RotateRow { y: usize, by: usize },
+    // rotate column x=A by B
+    RotateColumn { x: usize, by: usize },
+    // rect AxB at X,Y
+    RectAt { x: usize, y: usize, w: usize, h: usize },
+    // clear
+    Clear,
+    // invert rect AxB [at X,Y]
+    Invert { x: usize, y: usize, w: usize, h: usize },
+    // shift row y=A by B, pixels that fall off the right end are gone
+    ShiftRow { y: usize, by: usize },
+    // shift column x=A by B, pixels that fall off the bottom are gone
+    ShiftColumn { x: usize, by: usize },
+    // mirror row y=A
+    MirrorRow { y: usize },
+    // mirror column x=A
+    MirrorColumn { x: usize },
+}
+
+// Writes the operation the way it is written in the input.
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::Rect { w, h } => write!(f, "rect {}x{}", w, h),
+            Op::RotateRow { y, by } => write!(f, "rotate row y={} by {}", y, by),
+            Op::RotateColumn { x, by } => write!(f, "rotate column x={} by {}", x, by),
+            Op::RectAt { x, y, w, h } => write!(f, "rect {}x{} at {},{}", w, h, x, y),
+            Op::Clear => write!(f, "clear"),
+            Op::Invert { x, y, w, h } => write!(f, "invert rect {}x{} at {},{}", w, h, x, y),
+            Op::ShiftRow { y, by } => write!(f, "shift row y={} by {}", y, by),
+            Op::ShiftColumn { x, by } => write!(f, "shift column x={} by {}", x, by),
+            Op::MirrorRow { y } => write!(f, "mirror row y={}", y),
+            Op::MirrorColumn { x } => write!(f, "mirror column x={}", x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Empty,
+    UnknownOp(String),
+    // Holds the form the line should have had.
+    Syntax(&'static str),
+    BadNumber(String),
+    // A row or column that isn't on the screen.
+    OffScreen { what: &'static str, value: usize, count: usize },
+    // A rect dimension larger than the screen.
+    TooLarge { what: &'static str, value: usize, limit: usize },
+    EmptyScreen,
+    MisplacedHeader,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Empty => write!(f, "empty line"),
+            Problem::UnknownOp(ref op) => write!(f, "unknown operation `{}`", op),
+            Problem::Syntax(form) => write!(f, "expected `{}`", form),
+            Problem::BadNumber(ref s) => write!(f, "`{}` is not a number", s),
+            Problem::OffScreen { what, value, count } => {
+                write!(f, "{} {} is off the screen, which has {} {}s", what, value, count, what)
+            }
+            Problem::TooLarge { what, value, limit } => {
+                write!(f, "{} {} is larger than the screen's {}", what, value, limit)
+            }
+            Problem::EmptyScreen => write!(f, "the screen has to be at least 1x1"),
+            Problem::MisplacedHeader => write!(f, "`screen` has to be the first instruction"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // 1-based, like editors count them.
+    pub line: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.problem)
+    }
+}
+
+impl error::Error for ParseError {}
+
+const SCREEN: &str = "screen WxH";
+const RECT: &str = "rect AxB";
+const ROTATE_ROW: &str = "rotate row y=A by B";
+const ROTATE_COLUMN: &str = "rotate column x=A by B";
+const RECT_AT: &str = "rect AxB at X,Y";
+const CLEAR: &str = "clear";
+const INVERT: &str = "invert rect AxB [at X,Y]";
+const SHIFT_ROW: &str = "shift row y=A by B";
+const SHIFT_COLUMN: &str = "shift column x=A by B";
+const MIRROR_ROW: &str = "mirror row y=A";
+const MIRROR_COLUMN: &str = "mirror column x=A";
+// For lines that get no further than the first word.
+const ROTATE: &str = "rotate row y=A by B` or `rotate column x=A by B";
+const SHIFT: &str = "shift row y=A by B` or `shift column x=A by B";
+const MIRROR: &str = "mirror row y=A` or `mirror column x=A";
+
+// Every operation along with what it does, for the help text.
+pub const HELP: [(&str, &str); 10] = [
+    (RECT, "light the A by B pixels at the top left"),
+    (ROTATE_ROW, "move row A right by B, wrapping around"),
+    (ROTATE_COLUMN, "move column A down by B, wrapping around"),
+    (RECT_AT, "light the A by B pixels with X,Y at the top left"),
+    (CLEAR, "turn every pixel off"),
+    (INVERT, "flip the A by B pixels at X,Y, or at the top left"),
+    (SHIFT_ROW, "move row A right by B, turning off what comes in"),
+    (SHIFT_COLUMN, "move column A down by B, turning off what comes in"),
+    (MIRROR_ROW, "reverse row A"),
+    (MIRROR_COLUMN, "reverse column A"),
+];
+
+fn number(s: &str) -> Result<usize, Problem> {
+    s.parse().map_err(|_| Problem::BadNumber(s.to_string()))
+}
+
+// Parses the `A` of `x=A` or `y=A`.
+fn coordinate(s: &str, axis: &str, form: &'static str) -> Result<usize, Problem> {
+    match s.split_once('=') {
+        Some((a, n)) if a == axis => number(n),
+        _ => Err(Problem::Syntax(form)),
+    }
+}
+
+fn on_screen(what: &'static str, value: usize, count: usize) -> Result<usize, Problem> {
+    if value >= count {
+        return Err(Problem::OffScreen { what, value, count });
+    }
+    Ok(value)
+}
+
+fn fits(what: &'static str, value: usize, limit: usize) -> Result<usize, Problem> {
+    if value > limit {
+        return Err(Problem::TooLarge { what, value, limit });
+    }
+    Ok(value)
+}
+
+// Parses the `AxB` size and optional `X,Y` position of a rect, which has to fit on the screen.
+fn area(size: &str, at: Option<&str>, form: &'static str, width: usize, height: usize) -> Result<(usize, usize, usize, usize), Problem> {
+    let (w, h) = size.split_once('x').ok_or(Problem::Syntax(form))?;
+    let (w, h) = (number(w)?, number(h)?);
+    let (x, y) = match at {
+        Some(at) => {
+            let (x, y) = at.split_once(',').ok_or(Problem::Syntax(form))?;
+            (number(x)?, number(y)?)
+        }
+        None => (0, 0),
+    };
+    fits("right edge", x.saturating_add(w), width)?;
+    fits("bottom edge", y.saturating_add(h), height)?;
+    Ok((x, y, w, h))
+}
+
+// Parses a `WxH` screen size, as given in the header or on the command line.
+pub fn parse_size(s: &str) -> Result<(usize, usize), Problem> {
+    let (w, h) = s.split_once('x').ok_or(Problem::Syntax(SCREEN))?;
+    let size = (number(w)?, number(h)?);
+    if size.0 == 0 || size.1 == 0 {
+        return Err(Problem::EmptyScreen);
+    }
+    Ok(size)
+}
+
+// The first non-blank line of the input, if it is a `screen WxH` header.
+fn header(input: &str) -> Option<(usize, &str)> {
+    let (i, line) = input.lines().enumerate().find(|(_, l)| !l.trim().is_empty())?;
+    if line.split_whitespace().next() == Some("screen") {
+        return Some((i, line));
+    }
+    None
+}
+
+// The screen size from the header, if the input has one.
+pub fn screen_size(input: &str) -> Result<Option<(usize, usize)>, ParseError> {
+    let (i, line) = match header(input) {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let error = |problem| ParseError { line: i + 1, problem };
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        [_, size] => parse_size(size).map(Some).map_err(error),
+        _ => Err(error(Problem::Syntax(SCREEN))),
+    }
+}
+
+// Parses one instruction for a `width` by `height` screen. Rows and columns have to be on the
+// screen and rects have to fit on it.
+pub fn parse_op(line: &str, width: usize, height: usize) -> Result<Op, Problem> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words[..] {
+        ["rect", size] => {
+            let (w, h) = size.split_once('x').ok_or(Problem::Syntax(RECT))?;
+            Ok(Op::Rect {
+                w: fits("width", number(w)?, width)?,
+                h: fits("height", number(h)?, height)?,
+            })
+        }
+        ["rect", size, "at", at] => {
+            let (x, y, w, h) = area(size, Some(at), RECT_AT, width, height)?;
+            Ok(Op::RectAt { x, y, w, h })
+        }
+        ["rect", ..] => Err(Problem::Syntax(RECT)),
+        ["rotate", "row", y, "by", by] => Ok(Op::RotateRow {
+            y: on_screen("row", coordinate(y, "y", ROTATE_ROW)?, height)?,
+            by: number(by)?,
+        }),
+        ["rotate", "row", ..] => Err(Problem::Syntax(ROTATE_ROW)),
+        ["rotate", "column", x, "by", by] => Ok(Op::RotateColumn {
+            x: on_screen("column", coordinate(x, "x", ROTATE_COLUMN)?, width)?,
+            by: number(by)?,
+        }),
+        ["rotate", "column", ..] => Err(Problem::Syntax(ROTATE_COLUMN)),
+        ["rotate", ..] => Err(Problem::Syntax(ROTATE)),
+        ["clear"] => Ok(Op::Clear),
+        ["clear", ..] => Err(Problem::Syntax(CLEAR)),
+        ["invert", "rect", size] | ["invert", "rect", size, "at", _] => {
+            let (x, y, w, h) = area(size, words.get(4).cloned(), INVERT, width, height)?;
+            Ok(Op::Invert { x, y, w, h })
+        }
+        ["invert", ..] => Err(Problem::Syntax(INVERT)),
+        ["shift", "row", y, "by", by] => Ok(Op::ShiftRow {
+            y: on_screen("row", coordinate(y, "y", SHIFT_ROW)?, height)?,
+            by: number(by)?,
+        }),
+        ["shift", "row", ..] => Err(Problem::Syntax(SHIFT_ROW)),
+        ["shift", "column", x, "by", by] => Ok(Op::ShiftColumn {
+            x: on_screen("column", coordinate(x, "x", SHIFT_COLUMN)?, width)?,
+            by: number(by)?,
+        }),
+        ["shift", "column", ..] => Err(Problem::Syntax(SHIFT_COLUMN)),
+        ["shift", ..] => Err(Problem::Syntax(SHIFT)),
+        ["mirror", "row", y] => Ok(Op::MirrorRow {
+            y: on_screen("row", coordinate(y, "y", MIRROR_ROW)?, height)?,
+        }),
+        ["mirror", "row", ..] => Err(Problem::Syntax(MIRROR_ROW)),
+        ["mirror", "column", x] => Ok(Op::MirrorColumn {
+            x: on_screen("column", coordinate(x, "x", MIRROR_COLUMN)?, width)?,
+        }),
+        ["mirror", "column", ..] => Err(Problem::Syntax(MIRROR_COLUMN)),
+        ["mirror", ..] => Err(Problem::Syntax(MIRROR)),
+        ["screen", ..] => Err(Problem::MisplacedHeader),
+        [op, ..] => Err(Problem::UnknownOp(op.to_string())),
+        [] => Err(Problem::Empty),
+    }
+}
+
+// Parses every non-blank line of `input` after the header, stopping at the first bad one. Every
+// operation comes with its line number.
+pub fn parse(input: &str, width: usize, height: usize) -> Result<Vec<(usize, Op)>, ParseError> {
+    let header = header(input).map(|h| h.0);
+    let mut ops = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() || Some(i) == header {
+            continue;
+        }
+        let op = parse_op(line, width, height).map_err(|problem| ParseError { line: i + 1, problem })?;
+        ops.push((i + 1, op));
+    }
+    Ok(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> Problem {
+        parse_op(line, 7, 3).unwrap_err()
+    }
+
+    #[test]
+    fn new_forms() {
+        let ops = [
+            ("rect 2x2 at 3,1", Op::RectAt { x: 3, y: 1, w: 2, h: 2 }),
+            ("clear", Op::Clear),
+            ("invert rect 3x2", Op::Invert { x: 0, y: 0, w: 3, h: 2 }),
+            ("invert rect 2x1 at 5,2", Op::Invert { x: 5, y: 2, w: 2, h: 1 }),
+            ("shift row y=1 by 30", Op::ShiftRow { y: 1, by: 30 }),
+            ("shift column x=6 by 2", Op::ShiftColumn { x: 6, by: 2 }),
+            ("mirror row y=0", Op::MirrorRow { y: 0 }),
+            ("mirror column x=3", Op::MirrorColumn { x: 3 }),
+            ("  rect   7x3  at 0,0 ", Op::RectAt { x: 0, y: 0, w: 7, h: 3 }),
+        ];
+        for &(line, op) in &ops {
+            assert_eq!(parse_op(line, 7, 3), Ok(op), "{}", line);
+        }
+        // Invert without a position is written with one.
+        for &(_, op) in &ops[..8] {
+            assert_eq!(parse_op(&op.to_string(), 7, 3), Ok(op));
+        }
+    }
+
+    #[test]
+    fn rect_at_errors() {
+        assert_eq!(error("rect 2x2 at 3"), Problem::Syntax(RECT_AT));
+        assert_eq!(error("rect 2x2 at 3,1 now"), Problem::Syntax(RECT));
+        assert_eq!(error("rect 2x2 on 3,1"), Problem::Syntax(RECT));
+        assert_eq!(error("rect 2by2 at 3,1"), Problem::Syntax(RECT_AT));
+        assert_eq!(error("rect 2x2 at 3,y"), Problem::BadNumber("y".to_string()));
+        assert_eq!(error("rect 2x2 at 6,1"), Problem::TooLarge { what: "right edge", value: 8, limit: 7 });
+        assert_eq!(error("rect 1x2 at 0,2"), Problem::TooLarge { what: "bottom edge", value: 4, limit: 3 });
+        let huge = format!("rect 1x1 at {},0", usize::MAX);
+        assert_eq!(error(&huge), Problem::TooLarge { what: "right edge", value: usize::MAX, limit: 7 });
+    }
+
+    #[test]
+    fn clear_errors() {
+        assert_eq!(error("clear all"), Problem::Syntax(CLEAR));
+    }
+
+    #[test]
+    fn invert_errors() {
+        assert_eq!(error("invert"), Problem::Syntax(INVERT));
+        assert_eq!(error("invert row y=1"), Problem::Syntax(INVERT));
+        assert_eq!(error("invert rect 2x2 on 1,1"), Problem::Syntax(INVERT));
+        assert_eq!(error("invert rect 2x2 at 1"), Problem::Syntax(INVERT));
+        assert_eq!(error("invert rect 22 at 1,1"), Problem::Syntax(INVERT));
+        assert_eq!(error("invert rect ax2"), Problem::BadNumber("a".to_string()));
+        assert_eq!(error("invert rect 8x1"), Problem::TooLarge { what: "right edge", value: 8, limit: 7 });
+        assert_eq!(error("invert rect 1x1 at 0,3"), Problem::TooLarge { what: "bottom edge", value: 4, limit: 3 });
+    }
+
+    #[test]
+    fn shift_errors() {
+        assert_eq!(error("shift row y=1"), Problem::Syntax(SHIFT_ROW));
+        assert_eq!(error("shift row x=1 by 2"), Problem::Syntax(SHIFT_ROW));
+        assert_eq!(error("shift row y=-1 by 2"), Problem::BadNumber("-1".to_string()));
+        assert_eq!(error("shift row y=1 by two"), Problem::BadNumber("two".to_string()));
+        assert_eq!(error("shift row y=3 by 1"), Problem::OffScreen { what: "row", value: 3, count: 3 });
+        assert_eq!(error("shift column x=1 for 2"), Problem::Syntax(SHIFT_COLUMN));
+        assert_eq!(error("shift column y=1 by 2"), Problem::Syntax(SHIFT_COLUMN));
+        assert_eq!(error("shift column x=7 by 1"), Problem::OffScreen { what: "column", value: 7, count: 7 });
+        assert_eq!(error("shift"), Problem::Syntax(SHIFT));
+    }
+
+    #[test]
+    fn mirror_errors() {
+        assert_eq!(error("mirror row"), Problem::Syntax(MIRROR_ROW));
+        assert_eq!(error("mirror row y=1 by 2"), Problem::Syntax(MIRROR_ROW));
+        assert_eq!(error("mirror row x=1"), Problem::Syntax(MIRROR_ROW));
+        assert_eq!(error("mirror row y=3"), Problem::OffScreen { what: "row", value: 3, count: 3 });
+        assert_eq!(error("mirror column x="), Problem::BadNumber("".to_string()));
+        assert_eq!(error("mirror column x=7"), Problem::OffScreen { what: "column", value: 7, count: 7 });
+        assert_eq!(error("mirror rows y=1"), Problem::Syntax(MIRROR));
+    }
+
+    #[test]
+    fn known_words_of_the_wrong_shape() {
+        assert_eq!(error("rotate diagonal x=1"), Problem::Syntax(ROTATE));
+        assert_eq!(error("rotate"), Problem::Syntax(ROTATE));
+        assert_eq!(error("shift diagonal x=1 by 2"), Problem::Syntax(SHIFT));
+        assert_eq!(error("mirror"), Problem::Syntax(MIRROR));
+        assert_eq!(error("mirror y=1"), Problem::Syntax(MIRROR));
+        assert_eq!(error("reflect row y=1"), Problem::UnknownOp("reflect".to_string()));
+        assert_eq!(
+            ParseError { line: 3, problem: error("mirror rows y=1") }.to_string(),
+            "line 3: expected `mirror row y=A` or `mirror column x=A`"
+        );
+    }
+
+    #[test]
+    fn errors_come_with_their_line() {
+        let input = "screen 7x3\n\nrect 2x2 at 3,1\nclear\nshift row y=9 by 1\nclear\n";
+        let problem = Problem::OffScreen { what: "row", value: 9, count: 3 };
+        assert_eq!(parse(input, 7, 3), Err(ParseError { line: 5, problem }));
+        let ops = parse("screen 7x3\n\nrect 2x2 at 3,1\nclear\n", 7, 3).unwrap();
+        assert_eq!(ops, vec![(3, Op::RectAt { x: 3, y: 1, w: 2, h: 2 }), (4, Op::Clear)]);
+        let error = parse("clear\nscreen 7x3\n", 7, 3).unwrap_err();
+        assert_eq!(error, ParseError { line: 2, problem: Problem::MisplacedHeader });
+    }
+}
@@ -0,0 +1,360 @@
+use op::Op;
+
+const BITS: usize = 64;
+
+// Mask of the lowest `n` bits, for `n` up to 64.
+fn mask(n: usize) -> u64 {
+    if n == BITS {
+        !0
+    } else {
+        (1 << n) - 1
+    }
+}
+
+// Pixels packed 64 to a word, every row starting on a new word. Bit `x % 64` of word `x / 64`
+// is the pixel in column `x`, bits past the right edge are always off.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+    // Room for one row, so rotating a row doesn't need to allocate.
+    scratch: Vec<u64>,
+}
+
+impl Screen {
+    pub fn new(width: usize, height: usize) -> Screen {
+        let words_per_row = width.div_ceil(BITS);
+        Screen {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+            scratch: vec![0; words_per_row],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.row(y)[x / BITS] >> (x % BITS) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        let word = &mut self.words[y * self.words_per_row + x / BITS];
+        if on {
+            *word |= 1 << (x % BITS);
+        } else {
+            *word &= !(1 << (x % BITS));
+        }
+    }
+
+    pub fn lit_pixels(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+
+    // Combines every word covering the `w` by `h` rect at x,y with a mask of the pixels of the
+    // rect in it.
+    fn update<F: Fn(u64, u64) -> u64>(&mut self, x: usize, y: usize, w: usize, h: usize, f: F) {
+        if w == 0 {
+            return;
+        }
+        for row in y..y + h {
+            let start = row * self.words_per_row;
+            for i in x / BITS..(x + w).div_ceil(BITS) {
+                let from = x.max(i * BITS) - i * BITS;
+                let to = (x + w).min((i + 1) * BITS) - i * BITS;
+                let word = &mut self.words[start + i];
+                *word = f(*word, mask(to) & !mask(from));
+            }
+        }
+    }
+
+    // Shifts row `y` right by `by`, a word at a time. Pixels that fall off the right end come
+    // back in on the left.
+    fn rotate_row(&mut self, y: usize, by: usize) {
+        let by = by % self.width;
+        if by == 0 {
+            return;
+        }
+        let width = self.width;
+        let start = y * self.words_per_row;
+        let row = &self.words[start..start + self.words_per_row];
+        // `n` bits of the row starting at column `x`, which may straddle two words.
+        let bits = |x: usize, n: usize| {
+            let (i, shift) = (x / BITS, x % BITS);
+            let mut b = row[i] >> shift;
+            if shift > 0 && i + 1 < row.len() {
+                b |= row[i + 1] << (BITS - shift);
+            }
+            b & mask(n)
+        };
+        for (j, word) in self.scratch.iter_mut().enumerate() {
+            // Column `x` of the rotated row is column `x - by` of the original one.
+            let x = j * BITS;
+            let n = (width - x).min(BITS);
+            let from = (x + width - by) % width;
+            *word = if from + n <= width {
+                bits(from, n)
+            } else {
+                let head = width - from;
+                bits(from, head) | bits(0, n - head) << head
+            };
+        }
+        self.words[start..start + self.words_per_row].copy_from_slice(&self.scratch);
+    }
+
+    fn shift_row(&mut self, y: usize, by: usize) {
+        let by = by.min(self.width);
+        self.rotate_row(y, by);
+        self.update(0, y, by, 1, |word, mask| word & !mask);
+    }
+
+    fn shift_column(&mut self, x: usize, by: usize) {
+        let by = by.min(self.height);
+        self.rotate_column(x, by);
+        self.update(x, 0, 1, by, |word, mask| word & !mask);
+    }
+
+    fn mirror_row(&mut self, y: usize) {
+        let (mut left, mut right) = (0, self.width);
+        while left < right {
+            let (a, b) = (self.get(left, y), self.get(right - 1, y));
+            self.set(left, y, b);
+            self.set(right - 1, y, a);
+            left += 1;
+            right -= 1;
+        }
+    }
+
+    fn reverse_column(&mut self, x: usize, mut top: usize, mut bottom: usize) {
+        while top < bottom {
+            let (a, b) = (self.get(x, top), self.get(x, bottom - 1));
+            self.set(x, top, b);
+            self.set(x, bottom - 1, a);
+            top += 1;
+            bottom -= 1;
+        }
+    }
+
+    // Shifts column `x` down by `by` in place, by reversing the whole column and then both of
+    // the parts that have to swap places.
+    fn rotate_column(&mut self, x: usize, by: usize) {
+        let by = by % self.height;
+        let height = self.height;
+        self.reverse_column(x, 0, height);
+        self.reverse_column(x, 0, by);
+        self.reverse_column(x, by, height);
+    }
+
+    pub fn apply(&mut self, op: Op) {
+        match op {
+            Op::Rect { w, h } => self.update(0, 0, w, h, |word, mask| word | mask),
+            Op::RotateRow { y, by } => self.rotate_row(y, by),
+            Op::RotateColumn { x, by } => self.rotate_column(x, by),
+            Op::RectAt { x, y, w, h } => self.update(x, y, w, h, |word, mask| word | mask),
+            Op::Clear => self.words.iter_mut().for_each(|word| *word = 0),
+            Op::Invert { x, y, w, h } => self.update(x, y, w, h, |word, mask| word ^ mask),
+            Op::ShiftRow { y, by } => self.shift_row(y, by),
+            Op::ShiftColumn { x, by } => self.shift_column(x, by),
+            Op::MirrorRow { y } => self.mirror_row(y),
+            Op::MirrorColumn { x } => {
+                let height = self.height;
+                self.reverse_column(x, 0, height);
+            }
+        }
+    }
+
+    // Every pixel that differs from `before`, with whether it is lit now, row by row.
+    pub fn changes(&self, before: &Screen) -> Vec<(usize, usize, bool)> {
+        let mut changes = Vec::new();
+        for (i, (&now, &then)) in self.words.iter().zip(&before.words).enumerate() {
+            let mut changed = now ^ then;
+            while changed != 0 {
+                let bit = changed.trailing_zeros() as usize;
+                changed &= changed - 1;
+                let (y, x) = (i / self.words_per_row, i % self.words_per_row * BITS + bit);
+                changes.push((x, y, now >> bit & 1 == 1));
+            }
+        }
+        changes
+    }
+
+    // Reads a screen drawn with '#' for lit pixels and anything else for pixels that are off.
+    // Lines shorter than `width` are padded with pixels that are off, None if the drawing doesn't
+    // fit.
+    pub fn parse(drawing: &str, width: usize, height: usize) -> Option<Screen> {
+        let mut screen = Screen::new(width, height);
+        for (y, line) in drawing.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    if x >= width || y >= height {
+                        return None;
+                    }
+                    screen.set(x, y, true);
+                }
+            }
+        }
+        Some(screen)
+    }
+
+    pub fn draw(&self) {
+        for y in 0..self.height {
+            let row: String = (0..self.width).map(|x| if self.get(x, y) { '#' } else { ' ' }).collect();
+            println!("{}", row);
+        }
+    }
+}
+
+// The scratch row doesn't count.
+impl PartialEq for Screen {
+    fn eq(&self, other: &Screen) -> bool {
+        self.width == other.width && self.height == other.height && self.words == other.words
+    }
+}
+
+impl Eq for Screen {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use op;
+
+    fn screen(drawing: &str, width: usize, height: usize) -> Screen {
+        Screen::parse(drawing, width, height).unwrap()
+    }
+
+    fn applied(drawing: &str, width: usize, height: usize, op: Op) -> Screen {
+        let mut s = screen(drawing, width, height);
+        s.apply(op);
+        s
+    }
+
+    // Every frame of `extended`, worked out by hand.
+    #[test]
+    fn extended_frames() {
+        let frames = [
+            "...\n...##\n...##",
+            "###\n#####\n...##",
+            "###\n...####\n...##",
+            "####\n...####\n....#",
+            "####\n...#.##\n",
+            "...####\n...#.##\n",
+            "...####\n...#.##\n.....##",
+            "",
+            "\n\n......#",
+        ];
+        let input = include_str!("../extended");
+        assert_eq!(op::screen_size(input), Ok(Some((7, 3))));
+        let ops = op::parse(input, 7, 3).unwrap();
+        assert_eq!(ops.len(), frames.len());
+        let mut s = Screen::new(7, 3);
+        for (&(line, op), frame) in ops.iter().zip(&frames) {
+            s.apply(op);
+            assert!(s == screen(frame, 7, 3), "line {}: {}", line, op);
+        }
+        assert_eq!(s.lit_pixels(), 1);
+    }
+
+    #[test]
+    fn rect_at() {
+        let op = Op::RectAt { x: 1, y: 1, w: 3, h: 2 };
+        assert_eq!(applied("", 5, 4, op), screen("\n.###\n.###", 5, 4));
+        assert_eq!(applied("#...#\n.#", 5, 4, op), screen("#...#\n.###\n.###", 5, 4));
+        let op = Op::RectAt { x: 4, y: 3, w: 1, h: 1 };
+        assert_eq!(applied("", 5, 4, op), screen("\n\n\n....#", 5, 4));
+        let op = Op::RectAt { x: 2, y: 0, w: 0, h: 4 };
+        assert_eq!(applied("##", 5, 4, op), screen("##", 5, 4));
+        // Across the words of a wide row.
+        let op = Op::RectAt { x: 60, y: 0, w: 10, h: 1 };
+        let s = applied("", 100, 1, op);
+        assert_eq!(s.lit_pixels(), 10);
+        assert!((0..100).all(|x| s.get(x, 0) == (60..70).contains(&x)));
+    }
+
+    #[test]
+    fn invert() {
+        let drawing = "##..\n#.#.\n....";
+        let op = Op::Invert { x: 0, y: 0, w: 2, h: 2 };
+        assert_eq!(applied(drawing, 4, 3, op), screen("....\n.##.\n....", 4, 3));
+        let op = Op::Invert { x: 1, y: 1, w: 3, h: 2 };
+        let inverted = applied(drawing, 4, 3, op);
+        assert_eq!(inverted, screen("##..\n##.#\n.###", 4, 3));
+        assert_eq!(applied("##..\n##.#\n.###", 4, 3, op), screen(drawing, 4, 3));
+        let op = Op::Invert { x: 0, y: 0, w: 4, h: 3 };
+        assert_eq!(applied(drawing, 4, 3, op), screen("..##\n.#.#\n####", 4, 3));
+    }
+
+    #[test]
+    fn shift_row() {
+        let drawing = "#.##.#\n##";
+        let shifted = |by| applied(drawing, 6, 2, Op::ShiftRow { y: 0, by });
+        assert_eq!(shifted(0), screen(drawing, 6, 2));
+        assert_eq!(shifted(1), screen(".#.##.\n##", 6, 2));
+        assert_eq!(shifted(3), screen("...#.#\n##", 6, 2));
+        assert_eq!(shifted(5), screen(".....#\n##", 6, 2));
+        // Past the edge nothing is left, unlike rotating.
+        assert_eq!(shifted(6), screen("\n##", 6, 2));
+        assert_eq!(shifted(100), screen("\n##", 6, 2));
+        assert_eq!(applied(drawing, 6, 2, Op::RotateRow { y: 0, by: 6 }), screen(drawing, 6, 2));
+
+        let mut s = Screen::new(100, 1);
+        for x in 0..10 {
+            s.set(x, 0, true);
+        }
+        s.set(99, 0, true);
+        s.apply(Op::ShiftRow { y: 0, by: 58 });
+        assert!((0..100).all(|x| s.get(x, 0) == (58..68).contains(&x)));
+    }
+
+    #[test]
+    fn shift_column() {
+        let drawing = "#.\n..\n##\n#.";
+        let shifted = |by| applied(drawing, 2, 4, Op::ShiftColumn { x: 0, by });
+        assert_eq!(shifted(1), screen("..\n#.\n.#\n#.", 2, 4));
+        assert_eq!(shifted(2), screen("..\n..\n##\n..", 2, 4));
+        assert_eq!(shifted(3), screen("..\n..\n.#\n#.", 2, 4));
+        assert_eq!(shifted(4), screen("..\n..\n.#\n..", 2, 4));
+        assert_eq!(shifted(9), screen("..\n..\n.#\n..", 2, 4));
+        let s = applied(drawing, 2, 4, Op::ShiftColumn { x: 1, by: 1 });
+        assert_eq!(s, screen("#.\n..\n#.\n##", 2, 4));
+    }
+
+    #[test]
+    fn mirror() {
+        let drawing = "##..#\n.#...\n#....";
+        let row = |y| applied(drawing, 5, 3, Op::MirrorRow { y });
+        assert_eq!(row(0), screen("#..##\n.#...\n#....", 5, 3));
+        assert_eq!(row(1), screen("##..#\n...#.\n#....", 5, 3));
+        let column = |x| applied(drawing, 5, 3, Op::MirrorColumn { x });
+        assert_eq!(column(0), screen("##..#\n.#...\n#....", 5, 3));
+        assert_eq!(column(1), screen("#...#\n.#...\n##...", 5, 3));
+        assert_eq!(column(4), screen("##...\n.#...\n#...#", 5, 3));
+        // An even number of pixels.
+        assert_eq!(applied("#..", 3, 4, Op::MirrorColumn { x: 0 }), screen("\n\n\n#", 3, 4));
+
+        let mut s = Screen::new(70, 1);
+        s.set(1, 0, true);
+        s.set(64, 0, true);
+        s.apply(Op::MirrorRow { y: 0 });
+        assert!((0..70).all(|x| s.get(x, 0) == (x == 68 || x == 5)));
+    }
+
+    #[test]
+    fn clear() {
+        let mut s = screen("###\n#.#\n.##", 3, 3);
+        s.apply(Op::Clear);
+        assert_eq!(s, Screen::new(3, 3));
+    }
+}
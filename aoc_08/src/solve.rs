@@ -0,0 +1,386 @@
+use std::fmt;
+
+use op::Op;
+use screen::Screen;
+
+// The pixels of one rect that are lit at the end because of it, rather than a later rect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RectLit {
+    // Index of the rect in the operations, counting from 1.
+    pub step: usize,
+    pub w: usize,
+    pub h: usize,
+    // Row by row, `w` to a row.
+    pub lit: Vec<bool>,
+}
+
+// A pixel of the final screen that the operations don't leave the way it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub x: usize,
+    pub y: usize,
+    pub lit: bool,
+    // The last step that lit or turned off the pixel, if any did.
+    pub step: Option<usize>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (is, should) = if self.lit { ("lit", "off") } else { ("off", "lit") };
+        write!(f, "pixel {},{} is {} but the operations leave it {}", self.x, self.y, is, should)?;
+        match self.step {
+            Some(step) => write!(f, " (last set by step {})", step),
+            None => Ok(()),
+        }
+    }
+}
+
+// Pixels of the final screen, followed backwards through the operations.
+struct Trace {
+    width: usize,
+    height: usize,
+    // `at[y * width + x]` is the pixel of the final screen that is at x,y at the current step,
+    // None once it is one that gets shifted off the screen before the end.
+    at: Vec<Option<usize>>,
+    // The last step that lit or turned off each pixel, and which of the two it did.
+    set_by: Vec<Option<(usize, bool)>>,
+    // Whether each pixel is inverted an odd number of times after that.
+    flipped: Vec<bool>,
+}
+
+impl Trace {
+    fn area(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<usize> {
+        (y..y + h).flat_map(|y| (x..x + w).map(move |x| y * self.width + x)).collect()
+    }
+
+    fn column(&self, x: usize) -> Vec<usize> {
+        (0..self.height).map(|y| y * self.width + x).collect()
+    }
+
+    fn set(&mut self, positions: &[usize], step: usize, lit: bool) {
+        for &position in positions {
+            if let Some(pixel) = self.at[position] {
+                if self.set_by[pixel].is_none() {
+                    self.set_by[pixel] = Some((step, lit));
+                }
+            }
+        }
+    }
+
+    fn invert(&mut self, positions: &[usize]) {
+        for &position in positions {
+            if let Some(pixel) = self.at[position] {
+                if self.set_by[pixel].is_none() {
+                    self.flipped[pixel] = !self.flipped[pixel];
+                }
+            }
+        }
+    }
+
+    // Moves what is at `positions[i]` to `positions[i - by]`, the last `by` positions get
+    // pixels that don't make it to the end.
+    fn shift_back(&mut self, positions: &[usize], by: usize, wrap: bool) {
+        let mut moved: Vec<Option<usize>> = positions.iter().map(|&p| self.at[p]).collect();
+        moved.rotate_left(by);
+        if !wrap {
+            let len = moved.len();
+            for pixel in &mut moved[len - by..] {
+                *pixel = None;
+            }
+        }
+        for (&position, pixel) in positions.iter().zip(moved) {
+            self.at[position] = pixel;
+        }
+    }
+
+    fn undo(&mut self, step: usize, op: Op) {
+        let (width, height) = (self.width, self.height);
+        let row = |y: usize| (y * width..(y + 1) * width).collect::<Vec<_>>();
+        match op {
+            Op::Rect { w, h } => self.set(&self.area(0, 0, w, h), step, true),
+            Op::RectAt { x, y, w, h } => self.set(&self.area(x, y, w, h), step, true),
+            Op::Clear => self.set(&self.area(0, 0, width, height), step, false),
+            Op::Invert { x, y, w, h } => self.invert(&self.area(x, y, w, h)),
+            // Undoing a rotation right by `by` is a rotation left by `by`.
+            Op::RotateRow { y, by } => self.shift_back(&row(y), by % width, true),
+            Op::RotateColumn { x, by } => self.shift_back(&self.column(x), by % height, true),
+            // The pixels a shift brings in are off.
+            Op::ShiftRow { y, by } => {
+                let by = by.min(width);
+                self.set(&row(y)[..by], step, false);
+                self.shift_back(&row(y), by, false);
+            }
+            Op::ShiftColumn { x, by } => {
+                let by = by.min(height);
+                self.set(&self.column(x)[..by], step, false);
+                self.shift_back(&self.column(x), by, false);
+            }
+            Op::MirrorRow { y } => {
+                let mut row = row(y);
+                let pixels: Vec<Option<usize>> = row.iter().map(|&p| self.at[p]).collect();
+                row.reverse();
+                for (position, pixel) in row.into_iter().zip(pixels) {
+                    self.at[position] = pixel;
+                }
+            }
+            Op::MirrorColumn { x } => {
+                let mut column = self.column(x);
+                let pixels: Vec<Option<usize>> = column.iter().map(|&p| self.at[p]).collect();
+                column.reverse();
+                for (position, pixel) in column.into_iter().zip(pixels) {
+                    self.at[position] = pixel;
+                }
+            }
+        }
+    }
+}
+
+// Runs `ops` backwards from `screen`. Apart from the ones that light or turn off pixels, the
+// operations only move pixels around or invert them, so every pixel of the final screen can be
+// followed back to the last operation that lit or turned it off. For a rect that's the one that
+// must have lit it. Pixels that disagree with that are returned as conflicts.
+pub fn explain(ops: &[Op], screen: &Screen) -> (Vec<RectLit>, Vec<Conflict>) {
+    let (width, height) = (screen.width(), screen.height());
+    let mut trace = Trace {
+        width,
+        height,
+        at: (0..width * height).map(Some).collect(),
+        set_by: vec![None; width * height],
+        flipped: vec![false; width * height],
+    };
+    let mut rects = Vec::new();
+
+    for (i, &op) in ops.iter().enumerate().rev() {
+        trace.undo(i + 1, op);
+        let (x, y, w, h) = match op {
+            Op::Rect { w, h } => (0, 0, w, h),
+            Op::RectAt { x, y, w, h } => (x, y, w, h),
+            _ => continue,
+        };
+        let lit = trace
+            .area(x, y, w, h)
+            .into_iter()
+            .map(|position| match trace.at[position] {
+                Some(pixel) => trace.set_by[pixel] == Some((i + 1, true)) && !trace.flipped[pixel],
+                None => false,
+            })
+            .collect();
+        rects.push(RectLit { step: i + 1, w, h, lit });
+    }
+    rects.reverse();
+
+    let mut conflicts = Vec::new();
+    for pixel in 0..width * height {
+        let (x, y) = (pixel % width, pixel / width);
+        let set_by = trace.set_by[pixel];
+        let expected = set_by.is_some_and(|(_, lit)| lit) != trace.flipped[pixel];
+        let lit = screen.get(x, y);
+        if lit != expected {
+            conflicts.push(Conflict { x, y, lit, step: set_by.map(|(step, _)| step) });
+        }
+    }
+    (rects, conflicts)
+}
+
+// Builds `image` a row at a time. Every row with lit pixels is drawn into the top row, one
+// `rect Nx1` per run of lit pixels with a rotation in between, and then pushed down to make room
+// for the next one by rotating the columns that have anything in them. Rows are drawn from the
+// bottom up, so the pushes leave every one of them where it belongs.
+fn by_rows(image: &[Vec<bool>]) -> Vec<Op> {
+    let height = image.len();
+    let width = image.first().map_or(0, |row| row.len());
+    let mut ops = Vec::new();
+    let mut used = vec![false; width];
+    let mut previous = None;
+
+    for y in (0..height).rev() {
+        // (start, length) of every run, rightmost first.
+        let mut runs = Vec::new();
+        let mut x = width;
+        while x > 0 {
+            if !image[y][x - 1] {
+                x -= 1;
+                continue;
+            }
+            let end = x;
+            while x > 0 && image[y][x - 1] {
+                x -= 1;
+            }
+            runs.push((x, end - x));
+        }
+        if runs.is_empty() {
+            continue;
+        }
+
+        if let Some(previous) = previous {
+            push_down(&used, previous - y, &mut ops);
+        }
+        let mut drawn_at = 0;
+        for (i, &(start, len)) in runs.iter().enumerate() {
+            if i > 0 {
+                ops.push(Op::RotateRow { y: 0, by: drawn_at - start });
+            }
+            ops.push(Op::Rect { w: len, h: 1 });
+            drawn_at = start;
+            for u in &mut used[start..start + len] {
+                *u = true;
+            }
+        }
+        if drawn_at > 0 {
+            ops.push(Op::RotateRow { y: 0, by: drawn_at });
+        }
+        previous = Some(y);
+    }
+    if let Some(previous) = previous {
+        push_down(&used, previous, &mut ops);
+    }
+    ops
+}
+
+fn push_down(used: &[bool], by: usize, ops: &mut Vec<Op>) {
+    if by == 0 {
+        return;
+    }
+    for (x, _) in used.iter().enumerate().filter(|&(_, &u)| u) {
+        ops.push(Op::RotateColumn { x, by });
+    }
+}
+
+fn transposed(op: Op) -> Op {
+    match op {
+        Op::Rect { w, h } => Op::Rect { w: h, h: w },
+        Op::RotateRow { y, by } => Op::RotateColumn { x: y, by },
+        Op::RotateColumn { x, by } => Op::RotateRow { y: x, by },
+        _ => unreachable!("by_rows only uses rects and rotations"),
+    }
+}
+
+// A sequence of operations that draws `image` on a blank screen of the same size. The image is
+// built both a row and a column at a time, and the shorter of the two is returned.
+pub fn synthesize(image: &Screen) -> Vec<Op> {
+    let (width, height) = (image.width(), image.height());
+    let rows: Vec<Vec<bool>> = (0..height).map(|y| (0..width).map(|x| image.get(x, y)).collect()).collect();
+    let columns: Vec<Vec<bool>> = (0..width).map(|x| (0..height).map(|y| image.get(x, y)).collect()).collect();
+
+    let row_ops = by_rows(&rows);
+    let column_ops: Vec<Op> = by_rows(&columns).into_iter().map(transposed).collect();
+    if column_ops.len() < row_ops.len() {
+        column_ops
+    } else {
+        row_ops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use op;
+
+    const EXAMPLE: &str = "rect 3x2\nrotate column x=1 by 1\nrotate row y=0 by 4\nrotate column x=1 by 1\n";
+
+    fn screen(drawing: &str, width: usize, height: usize) -> Screen {
+        Screen::parse(drawing, width, height).unwrap()
+    }
+
+    fn replayed(ops: &[Op], width: usize, height: usize) -> Screen {
+        let mut screen = Screen::new(width, height);
+        for &op in ops {
+            screen.apply(op);
+        }
+        screen
+    }
+
+    fn assert_synthesized(image: &Screen) {
+        let ops = synthesize(image);
+        assert!(replayed(&ops, image.width(), image.height()) == *image, "{:?} draws something else", ops);
+    }
+
+    #[test]
+    fn synthesizes_drawings() {
+        for &(drawing, width, height) in &[
+            (".#..#.#\n#.#....\n.#.....", 7, 3),
+            ("", 4, 3),
+            ("####\n####\n####", 4, 3),
+            ("#.#.#\n.#.#.\n#.#.#\n.#.#.", 5, 4),
+            ("....#\n\n\n#", 5, 4),
+            ("#", 1, 1),
+            ("", 1, 1),
+            ("#..##.#", 7, 1),
+            ("#######", 7, 1),
+            ("#\n.\n.\n#\n#\n.\n#", 1, 7),
+            ("#\n#\n#", 1, 3),
+        ] {
+            assert_synthesized(&screen(drawing, width, height));
+        }
+        let ops = op::parse(include_str!("../input"), 50, 6).unwrap();
+        let ops: Vec<Op> = ops.into_iter().map(|(_, op)| op).collect();
+        assert_synthesized(&replayed(&ops, 50, 6));
+    }
+
+    #[test]
+    fn synthesizes_random_drawings() {
+        let mut state = 0x2016_0008u64;
+        for &(width, height) in &[(1, 9), (9, 1), (8, 8), (50, 6), (13, 70)] {
+            let mut image = Screen::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    image.set(x, y, state.is_multiple_of(3));
+                }
+            }
+            assert_synthesized(&image);
+        }
+    }
+
+    #[test]
+    fn explains_the_example() {
+        let ops: Vec<Op> = op::parse(EXAMPLE, 7, 3).unwrap().into_iter().map(|(_, op)| op).collect();
+        let final_screen = replayed(&ops, 7, 3);
+        assert_eq!(final_screen, screen(".#..#.#\n#.#....\n.#.....", 7, 3));
+        let (rects, conflicts) = explain(&ops, &final_screen);
+        assert_eq!(rects, vec![RectLit { step: 1, w: 3, h: 2, lit: vec![true; 6] }]);
+        assert_eq!(conflicts, vec![]);
+    }
+
+    #[test]
+    fn explains_what_later_operations_undo() {
+        let ops = [
+            Op::Rect { w: 3, h: 2 },
+            Op::RectAt { x: 2, y: 0, w: 2, h: 1 },
+            Op::Invert { x: 0, y: 1, w: 1, h: 1 },
+            Op::ShiftRow { y: 1, by: 2 },
+        ];
+        let final_screen = replayed(&ops, 4, 2);
+        assert_eq!(final_screen, screen("####\n...#", 4, 2));
+        let (rects, conflicts) = explain(&ops, &final_screen);
+        assert_eq!(
+            rects,
+            vec![
+                RectLit { step: 1, w: 3, h: 2, lit: vec![true, true, false, false, true, false] },
+                RectLit { step: 2, w: 2, h: 1, lit: vec![true, true] },
+            ]
+        );
+        assert_eq!(conflicts, vec![]);
+    }
+
+    #[test]
+    fn pixels_that_contradict_the_operations_are_conflicts() {
+        let ops: Vec<Op> = op::parse(EXAMPLE, 7, 3).unwrap().into_iter().map(|(_, op)| op).collect();
+        let (_, conflicts) = explain(&ops, &screen("....#.#\n#.#....\n.#....#", 7, 3));
+        assert_eq!(
+            conflicts,
+            vec![
+                Conflict { x: 1, y: 0, lit: false, step: Some(1) },
+                Conflict { x: 6, y: 2, lit: true, step: None },
+            ]
+        );
+        assert_eq!(conflicts[0].to_string(), "pixel 1,0 is off but the operations leave it lit (last set by step 1)");
+        assert_eq!(conflicts[1].to_string(), "pixel 6,2 is lit but the operations leave it off");
+
+        let ops = [Op::Rect { w: 2, h: 1 }, Op::Clear];
+        let (_, conflicts) = explain(&ops, &screen(".#", 2, 1));
+        assert_eq!(conflicts, vec![Conflict { x: 1, y: 0, lit: true, step: Some(2) }]);
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Instant;
+
+use decompress::{self, Options, Version};
+
+// Small xorshift generator, good enough for making up test data. The same seed always makes up
+// the same data.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+// Appends a random piece of version two data nested up to `depth` markers deep.
+fn piece(rng: &mut Rng, depth: usize, out: &mut Vec<u8>) {
+    for _ in 0..1 + rng.next(4) {
+        if depth == 0 || rng.next(3) == 0 {
+            for _ in 0..1 + rng.next(12) {
+                out.push(b'A' + rng.next(26) as u8);
+            }
+            continue;
+        }
+        let mut span = Vec::new();
+        piece(rng, depth - 1, &mut span);
+        out.extend_from_slice(format!("({}x{})", span.len(), 1 + rng.next(9)).as_bytes());
+        out.extend_from_slice(&span);
+    }
+}
+
+// Makes up `size` bytes or so of version two data with lots of top level markers.
+pub fn synthetic_input(size: usize) -> Vec<u8> {
+    let mut rng = Rng(0x2016_0009);
+    let mut data = Vec::with_capacity(size + 1024);
+    while data.len() < size {
+        piece(&mut rng, 6, &mut data);
+    }
+    data
+}
+
+// Times the sequential and parallel version two length computations on `size` bytes of
+// synthetic input.
+pub fn run(size: usize, threads: usize) {
+    let data = synthetic_input(size);
+    let options = Options {
+        version: Version::Two,
+        ..Options::default()
+    };
+
+    let start = Instant::now();
+    let sequential = decompress::decompressed_len(&data, options).expect("Bad synthetic input.");
+    let sequential_time = start.elapsed();
+
+    let start = Instant::now();
+    let parallel = decompress::decompressed_len_parallel(&data, options, threads).expect("Bad synthetic input.");
+    let parallel_time = start.elapsed();
+
+    assert_eq!(sequential, parallel, "Parallel length differs from the sequential one.");
+    println!("Input: {} bytes, decompressed length (v2): {}", data.len(), sequential);
+    println!("Sequential: {:?}", sequential_time);
+    println!("Parallel ({} threads): {:?}", threads, parallel_time);
+    println!("Speedup: {:.2}x", sequential_time.as_secs_f64() / parallel_time.as_secs_f64());
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+use std::ops::{AddAssign, MulAssign};
+
+// Unsigned integer of any size, for decompressed lengths that don't fit in a u64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigUint {
+    // Least significant first, no trailing zeros.
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    pub fn new(n: u64) -> BigUint {
+        let mut limbs = vec![n];
+        limbs.retain(|l| *l != 0);
+        BigUint { limbs }
+    }
+
+    // Reads a number written in decimal, which has to be all digits.
+    pub fn parse(digits: &[u8]) -> BigUint {
+        let mut n = BigUint::new(0);
+        for d in digits {
+            n *= 10;
+            n += &BigUint::new((d - b'0') as u64);
+        }
+        n
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+        let mut carry = false;
+        for i in 0..self.limbs.len() {
+            let (sum, c1) = self.limbs[i].overflowing_add(other.limbs.get(i).cloned().unwrap_or(0));
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            self.limbs[i] = sum;
+            carry = c1 || c2;
+            if !carry && i >= other.limbs.len() {
+                break;
+            }
+        }
+        if carry {
+            self.limbs.push(1);
+        }
+    }
+}
+
+impl MulAssign<u64> for BigUint {
+    fn mul_assign(&mut self, n: u64) {
+        let mut carry = 0;
+        for limb in &mut self.limbs {
+            let product = *limb as u128 * n as u128 + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u64);
+        }
+        if n == 0 {
+            self.limbs.clear();
+        }
+    }
+}
+
+impl MulAssign<&BigUint> for BigUint {
+    fn mul_assign(&mut self, other: &BigUint) {
+        let mut product = BigUint::new(0);
+        for (i, &limb) in other.limbs.iter().enumerate() {
+            let mut part = self.clone();
+            part *= limb;
+            if !part.limbs.is_empty() {
+                part.limbs.splice(0..0, vec![0; i]);
+            }
+            product += &part;
+        }
+        *self = product;
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+
+        // Repeatedly divide by 10^19, collecting the remainders as 19 digit chunks.
+        let mut limbs = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !limbs.is_empty() {
+            let mut rem = 0u128;
+            for limb in limbs.iter_mut().rev() {
+                let cur = (rem << 64) | *limb as u128;
+                *limb = (cur / CHUNK) as u64;
+                rem = cur % CHUNK;
+            }
+            chunks.push(rem as u64);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+
+        match chunks.pop() {
+            Some(first) => write!(f, "{}", first)?,
+            None => return write!(f, "0"),
+        }
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:019}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sum(a: &BigUint, b: &BigUint) -> BigUint {
+        let mut sum = a.clone();
+        sum += b;
+        sum
+    }
+
+    #[test]
+    fn adds_with_carries() {
+        let max = BigUint::new(u64::MAX);
+        let one = BigUint::new(1);
+        assert_eq!(sum(&max, &one).to_string(), "18446744073709551616");
+        assert_eq!(sum(&one, &max).to_string(), "18446744073709551616");
+        assert_eq!(sum(&max, &max).to_string(), "36893488147419103230");
+        assert_eq!(sum(&BigUint::new(0), &max), max);
+
+        // 2^128 - 1, the carry has to go through both limbs.
+        let mut all_ones = max.clone();
+        all_ones *= u64::MAX;
+        all_ones += &sum(&max, &max);
+        assert_eq!(all_ones.to_string(), "340282366920938463463374607431768211455");
+        assert_eq!(sum(&all_ones, &one).to_string(), "340282366920938463463374607431768211456");
+        assert_eq!(sum(&one, &all_ones).to_string(), "340282366920938463463374607431768211456");
+        assert_eq!(sum(&all_ones, &all_ones).to_string(), "680564733841876926926749214863536422910");
+    }
+
+    #[test]
+    fn multiplies() {
+        let mut n = BigUint::new(u64::MAX);
+        n *= u64::MAX;
+        assert_eq!(n.to_string(), "340282366920938463426481119284349108225");
+        n *= 3;
+        assert_eq!(n.to_string(), "1020847100762815390279443357853047324675");
+        n *= 1;
+        assert_eq!(n.to_string(), "1020847100762815390279443357853047324675");
+        n *= 0;
+        assert_eq!(n, BigUint::new(0));
+        assert_eq!(n.to_string(), "0");
+        n += &BigUint::new(7);
+        assert_eq!(n.to_string(), "7");
+
+        let mut n = BigUint::parse(b"340282366920938463463374607431768211455");
+        n *= &BigUint::parse(b"18446744073709551617");
+        assert_eq!(n.to_string(), "6277101735386680764176071790128604879547283307822093172735");
+        n *= &BigUint::new(0);
+        assert_eq!(n, BigUint::new(0));
+    }
+
+    #[test]
+    fn prints_every_chunk_of_digits() {
+        assert_eq!(BigUint::new(0).to_string(), "0");
+        assert_eq!(BigUint::new(9_999_999_999_999_999_999).to_string(), "9999999999999999999");
+        assert_eq!(BigUint::new(10_000_000_000_000_000_000).to_string(), "10000000000000000000");
+        assert_eq!(BigUint::new(10_000_000_000_000_000_005).to_string(), "10000000000000000005");
+        for s in &[
+            "100000000000000000000000000000000000000",
+            "100000000000000000000000000000000000001",
+            "100000000000000000010000000000000000000",
+            "1000000000000000000000000000000000000000000000000000000000",
+            "12345678901234567890123456789012345678901234567890",
+        ] {
+            assert_eq!(BigUint::parse(s.as_bytes()).to_string(), *s);
+        }
+        assert_eq!(BigUint::parse(b"000"), BigUint::new(0));
+        assert_eq!(BigUint::parse(b"0018446744073709551615"), BigUint::new(u64::MAX));
+    }
+}
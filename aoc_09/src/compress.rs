@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::io;
+
+use decompress::Version;
+use marker::Marker;
+
+// Longest repeating unit the compressor looks for.
+const MAX_PERIOD: usize = 256;
+// Long runs only try this many of their smallest and largest repeat counts, the ones in between
+// never beat taking the whole run.
+const REPEAT_CANDIDATES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // Takes the run that saves the most at each position, linear in the input for most texts.
+    Greedy,
+    // Picks the cheapest combination of literals and runs, slow on large inputs. Literals are
+    // costed as one byte each, also a '(' that needs to be escaped.
+    Best,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Literal,
+    // `repeat` copies of the `period` characters at the current position.
+    Run { period: usize, repeat: usize },
+}
+
+fn digits(mut n: usize) -> usize {
+    let mut count = 1;
+    while n >= 10 {
+        n /= 10;
+        count += 1;
+    }
+    count
+}
+
+fn marker_len(len: usize, repeat: usize) -> usize {
+    digits(len) + digits(repeat) + 3
+}
+
+// Number of back to back copies of `text[i..i + period]` starting at `i`.
+fn copies(text: &[u8], i: usize, period: usize) -> usize {
+    let unit = &text[i..i + period];
+    text[i..].chunks(period).take_while(|c| *c == unit).count()
+}
+
+// Version one has to hide '(' inside the data of a marker, a single one covering the whole
+// stretch of literals is cheaper than one per '('.
+fn push_literals(literals: &[u8], steps: &mut Vec<Step>) {
+    if literals.contains(&b'(') {
+        steps.push(Step::Run { period: literals.len(), repeat: 1 });
+    } else {
+        steps.extend(literals.iter().map(|_| Step::Literal));
+    }
+}
+
+struct Compressor<'a> {
+    version: Version,
+    strategy: Strategy,
+    // Compressed units, only used by version two where the data of a marker is compressed too.
+    units: HashMap<&'a [u8], Vec<u8>>,
+}
+
+impl<'a> Compressor<'a> {
+    // The data that goes after the marker of a run of `unit`.
+    fn unit(&mut self, unit: &'a [u8]) -> &[u8] {
+        if self.version == Version::One {
+            return unit;
+        }
+        if !self.units.contains_key(unit) {
+            let encoded = self.compress(unit);
+            self.units.insert(unit, encoded);
+        }
+        &self.units[unit]
+    }
+
+    fn greedy(&self, text: &[u8]) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let mut literals = 0;
+        let mut i = 0;
+        while i < text.len() {
+            let mut best: Option<(usize, Step)> = None;
+            for period in 1..=MAX_PERIOD.min((text.len() - i) / 2) {
+                let repeat = copies(text, i, period);
+                if repeat < 2 {
+                    continue;
+                }
+                let saved = (period * (repeat - 1)).saturating_sub(marker_len(period, repeat));
+                if saved > best.map_or(0, |b| b.0) {
+                    best = Some((saved, Step::Run { period, repeat }));
+                }
+            }
+            match best {
+                Some((_, step @ Step::Run { period, repeat })) => {
+                    push_literals(&text[i - literals..i], &mut steps);
+                    literals = 0;
+                    steps.push(step);
+                    i += period * repeat;
+                }
+                _ => {
+                    literals += 1;
+                    i += 1;
+                }
+            }
+        }
+        push_literals(&text[i - literals..], &mut steps);
+        steps
+    }
+
+    fn best(&mut self, text: &'a [u8]) -> Vec<Step> {
+        let n = text.len();
+        let mut cost = vec![0; n + 1];
+        let mut choice = vec![Step::Literal; n];
+        // matching[p - 1] counts how many characters from `i` on equal the one `p` further along.
+        let mut matching = vec![0; MAX_PERIOD];
+
+        for i in (0..n).rev() {
+            cost[i] = 1 + cost[i + 1];
+            for period in 1..=MAX_PERIOD.min(n - i) {
+                let m = &mut matching[period - 1];
+                *m = if i + period < n && text[i] == text[i + period] { *m + 1 } else { 0 };
+                let max_repeat = 1 + *m / period;
+                if max_repeat < 2 {
+                    continue;
+                }
+
+                let unit_len = self.unit(&text[i..i + period]).len();
+                let low = 2..=max_repeat.min(REPEAT_CANDIDATES);
+                let high = max_repeat.saturating_sub(REPEAT_CANDIDATES).max(REPEAT_CANDIDATES) + 1..=max_repeat;
+                for repeat in low.chain(high) {
+                    let c = marker_len(unit_len, repeat) + unit_len + cost[i + period * repeat];
+                    if c < cost[i] {
+                        cost[i] = c;
+                        choice[i] = Step::Run { period, repeat };
+                    }
+                }
+            }
+        }
+
+        let mut steps = Vec::new();
+        let mut literals = 0;
+        let mut i = 0;
+        while i < n {
+            match choice[i] {
+                Step::Literal => {
+                    literals += 1;
+                    i += 1;
+                }
+                step @ Step::Run { period, repeat } => {
+                    push_literals(&text[i - literals..i], &mut steps);
+                    literals = 0;
+                    steps.push(step);
+                    i += period * repeat;
+                }
+            }
+        }
+        push_literals(&text[i - literals..], &mut steps);
+        steps
+    }
+
+    fn compress(&mut self, text: &'a [u8]) -> Vec<u8> {
+        let steps = match self.strategy {
+            Strategy::Greedy => self.greedy(text),
+            Strategy::Best => self.best(text),
+        };
+
+        let mut compressed = Vec::new();
+        let mut i = 0;
+        for step in steps {
+            match step {
+                Step::Literal => {
+                    compressed.push(text[i]);
+                    i += 1;
+                }
+                Step::Run { period, repeat } => {
+                    let unit = self.unit(&text[i..i + period]).to_vec();
+                    let marker = Marker { len: unit.len(), repeat };
+                    compressed.extend_from_slice(marker.to_string().as_bytes());
+                    compressed.extend_from_slice(&unit);
+                    i += period * repeat;
+                }
+            }
+        }
+        compressed
+    }
+}
+
+// Compresses `text` so that decompressing it with `version` gives back `text`. Version one
+// escapes '(' by wrapping it in a marker, version two has no way to do that so such text is
+// rejected.
+pub fn compress(text: &[u8], version: Version, strategy: Strategy) -> io::Result<Vec<u8>> {
+    if version == Version::Two && text.contains(&b'(') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "'(' can't be encoded in version two"));
+    }
+    let mut compressor = Compressor {
+        version,
+        strategy,
+        units: HashMap::new(),
+    };
+    Ok(compressor.compress(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bench::Rng;
+    use decompress::{self, Options};
+
+    // Text with runs of random units among random characters from `alphabet`.
+    fn random_text(rng: &mut Rng, alphabet: &[u8], len: usize) -> Vec<u8> {
+        let mut text = Vec::new();
+        while text.len() < len {
+            let unit: Vec<u8> = (0..1 + rng.next(12)).map(|_| alphabet[rng.next(alphabet.len())]).collect();
+            for _ in 0..1 + rng.next(6) {
+                text.extend_from_slice(&unit);
+            }
+        }
+        text.truncate(len);
+        text
+    }
+
+    fn assert_round_trip(text: &[u8], version: Version, strategy: Strategy) {
+        let compressed = compress(text, version, strategy).unwrap();
+        let options = Options {
+            version,
+            keep_whitespace: true,
+            ..Options::default()
+        };
+        let mut decompressed = Vec::new();
+        let len = decompress::decompress(&compressed[..], &mut decompressed, options).unwrap();
+        assert_eq!(len, text.len() as u64);
+        assert!(
+            decompressed == text,
+            "{:?} {:?}: {:?} compressed to {:?} decompresses to {:?}",
+            version,
+            strategy,
+            String::from_utf8_lossy(text),
+            String::from_utf8_lossy(&compressed),
+            String::from_utf8_lossy(&decompressed)
+        );
+    }
+
+    fn edge_cases() -> Vec<Vec<u8>> {
+        let mut texts: Vec<Vec<u8>> = ["", "A", "AA", "AAA", "ABABAB", "A B\tC\n", "  \n\n  \n\n", "(1x2)", "((((", "x)(x"]
+            .iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect();
+        texts.push(vec![b'A'; 300]);
+        texts.push(b"XYZ".repeat(100));
+        texts.push(b"(3x3)".repeat(60));
+        texts.push(b" \n".repeat(150));
+        texts.push(vec![b'B'; MAX_PERIOD + 1]);
+        texts
+    }
+
+    // Best is too slow for these.
+    #[test]
+    fn long_runs_round_trip() {
+        let mut texts = vec![vec![b'A'; 100_000], b"XYZ".repeat(20_000), b" \n\t".repeat(10_000)];
+        texts.push([&b"head"[..], &vec![b'B'; MAX_PERIOD * 40 + 7], b"tail"].concat());
+        for text in texts {
+            assert_round_trip(&text, Version::One, Strategy::Greedy);
+            assert_round_trip(&text, Version::Two, Strategy::Greedy);
+        }
+        assert_round_trip(&b"(1x1)".repeat(20_000), Version::One, Strategy::Greedy);
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let alphabets: [&[u8]; 3] = [b"AB", b"ABCDEFGH \n\t", b"AB(x)12 "];
+        for &strategy in &[Strategy::Greedy, Strategy::Best] {
+            for text in edge_cases() {
+                assert_round_trip(&text, Version::One, strategy);
+                if !text.contains(&b'(') {
+                    assert_round_trip(&text, Version::Two, strategy);
+                }
+            }
+            for &alphabet in &alphabets {
+                for len in [1, 7, 50, 300, 600] {
+                    let text = random_text(&mut rng, alphabet, len);
+                    assert_round_trip(&text, Version::One, strategy);
+                    if !alphabet.contains(&b'(') {
+                        assert_round_trip(&text, Version::Two, strategy);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn runs_get_shorter() {
+        for &version in &[Version::One, Version::Two] {
+            for &strategy in &[Strategy::Greedy, Strategy::Best] {
+                assert!(compress(b"AAAAAAAAAA", version, strategy).unwrap().len() < 10);
+                assert!(compress(&b"XYZ".repeat(100), version, strategy).unwrap().len() < 20);
+            }
+        }
+        assert_eq!(compress(b"ABC", Version::One, Strategy::Greedy).unwrap(), b"ABC");
+    }
+
+    #[test]
+    fn version_two_rejects_parens() {
+        for &strategy in &[Strategy::Greedy, Strategy::Best] {
+            assert!(compress(b"A(B", Version::Two, strategy).is_err());
+            assert_round_trip(b"A(B", Version::One, strategy);
+        }
+    }
+}
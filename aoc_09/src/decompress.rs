@@ -0,0 +1,629 @@
+use std::io::{self, Read, Write};
+use std::thread;
+
+use bignum::BigUint;
+use error::{self, Error, Malformed};
+use marker::{self, Marker, Mode, Token, MAX_MARKER_LEN};
+use whitespace::{self, Whitespace};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+// Deepest markers are followed into. Every level of nesting is a recursive call, so crafted input
+// nested any deeper would run out of stack.
+pub const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    // Data referenced by a marker is copied as is.
+    One,
+    // Markers within data referenced by a marker are decompressed as well.
+    Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    pub version: Version,
+    pub mode: Mode,
+    // Whitespace is removed before looking for markers unless this is set.
+    pub keep_whitespace: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            version: Version::One,
+            mode: Mode::Strict,
+            keep_whitespace: false,
+        }
+    }
+}
+
+// Sliding window over a reader. It only ever holds the marker or marker span currently being
+// decoded, so memory use is bounded by the compressed input and never by the decompressed size.
+struct Window<R> {
+    reader: R,
+    buf: Vec<u8>,
+    pos: usize,
+    // Offset of `pos` in the input, not counting stripped whitespace.
+    offset: u64,
+    eof: bool,
+    whitespace: Option<Whitespace>,
+}
+
+impl<R: Read> Window<R> {
+    fn new(reader: R, keep_whitespace: bool) -> Window<R> {
+        Window {
+            reader,
+            buf: Vec::new(),
+            pos: 0,
+            offset: 0,
+            eof: false,
+            whitespace: if keep_whitespace { None } else { Some(Whitespace::new()) },
+        }
+    }
+
+    // Returns the buffered data, reading until at least `n` bytes are available or the input ends.
+    // The buffer grows a chunk at a time, so asking for more than the input has only costs as much
+    // memory as what is left of the input.
+    fn fill(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.buf.len() - self.pos < n && self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        while self.buf.len() - self.pos < n && !self.eof {
+            let len = self.buf.len();
+            self.buf.resize(len + CHUNK_SIZE, 0);
+            match self.reader.read(&mut self.buf[len..]) {
+                Ok(read) => {
+                    let kept = match self.whitespace {
+                        Some(ref mut whitespace) => whitespace.strip(&mut self.buf[len..len + read]),
+                        None => read,
+                    };
+                    self.buf.truncate(len + kept);
+                    self.eof = read == 0;
+                }
+                Err(e) => {
+                    self.buf.truncate(len);
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.pos += n;
+        self.offset += n as u64;
+        if let Some(ref mut whitespace) = self.whitespace {
+            whitespace.forget(self.offset);
+        }
+    }
+
+    fn original_error(&self, e: Error) -> Error {
+        match self.whitespace {
+            Some(ref whitespace) => whitespace.original_error(e),
+            None => e,
+        }
+    }
+}
+
+// The nesting depth of the span of the marker at `offset`, found in data nested `depth` deep.
+// Fails if that is deeper than MAX_DEPTH.
+pub fn nested(depth: usize, offset: u64) -> Result<usize, Error> {
+    if depth >= MAX_DEPTH {
+        return Err(Error::DepthLimit { offset, limit: MAX_DEPTH });
+    }
+    Ok(depth + 1)
+}
+
+// Writes `span`, found at `offset` in the input and nested `depth` deep, decompressed `repeat`
+// times. Returns the number of bytes written.
+fn expand<W: Write>(span: &[u8], offset: u64, depth: usize, repeat: usize, options: Options, writer: &mut W) -> Result<u64, Error> {
+    // Markers in a span repeated no times still have to be good, just like when working out the
+    // length.
+    if repeat == 0 && options.version == Version::Two {
+        decompress_slice(span, offset, depth, options, &mut io::sink())?;
+    }
+    let mut written = 0;
+    for _ in 0..repeat {
+        written += match options.version {
+            Version::One => {
+                writer.write_all(span)?;
+                span.len() as u64
+            }
+            Version::Two => decompress_slice(span, offset, depth, options, writer)?,
+        };
+    }
+    Ok(written)
+}
+
+fn decompress_slice<W: Write>(data: &[u8], offset: u64, depth: usize, options: Options, writer: &mut W) -> Result<u64, Error> {
+    let mut written = 0;
+    for token in marker::tokenize(data, offset, options.mode) {
+        written += match token? {
+            (_, Token::Literal(s)) => {
+                writer.write_all(s)?;
+                s.len() as u64
+            }
+            (offset, Token::Marker { marker, header, span }) => {
+                let depth = nested(depth, offset)?;
+                expand(span, offset + header.len() as u64, depth, marker.repeat, options, writer)?
+            }
+        };
+    }
+    Ok(written)
+}
+
+// Decompresses everything from `reader` into `writer` as it goes, returning the decompressed
+// length. Only the span of one top level marker is held in memory at a time.
+pub fn decompress<R: Read, W: Write>(reader: R, writer: &mut W, options: Options) -> Result<u64, Error> {
+    let mut window = Window::new(reader, options.keep_whitespace);
+    let result = decompress_window(&mut window, writer, options);
+    result.map_err(|e| window.original_error(e))
+}
+
+fn decompress_window<R: Read, W: Write>(window: &mut Window<R>, writer: &mut W, options: Options) -> Result<u64, Error> {
+    let mut written = 0;
+    loop {
+        let data = window.fill(MAX_MARKER_LEN)?;
+        if data.is_empty() {
+            break;
+        }
+        let consumed = match data.iter().position(|b| *b == b'(') {
+            Some(0) => {
+                let parsed = Marker::parse(data);
+                let offset = window.offset;
+                let marker = match parsed {
+                    Ok((marker, n)) => {
+                        // A span too long to add up to is an overrun all the same, reading the
+                        // rest of the input only tells how much there is.
+                        let wanted = n.checked_add(marker.len);
+                        let data = window.fill(wanted.unwrap_or(usize::MAX))?;
+                        match wanted {
+                            Some(wanted) if data.len() >= wanted => {
+                                let span = &data[n..wanted];
+                                written += expand(span, offset + n as u64, 1, marker.repeat, options, writer)?;
+                                Ok(wanted)
+                            }
+                            _ => Err(Malformed::Overrun { len: marker.len, available: data.len() - n }),
+                        }
+                    }
+                    Err(problem) => Err(problem),
+                };
+                match (marker, options.mode) {
+                    (Ok(n), _) => n,
+                    (Err(Malformed::TooLarge), _) => return Err(Error::TooLarge { offset }),
+                    (Err(problem), Mode::Strict) => return Err(Error::Marker { offset, problem }),
+                    (Err(_), Mode::Lenient) => {
+                        writer.write_all(b"(")?;
+                        written += 1;
+                        1
+                    }
+                }
+            }
+            Some(i) => {
+                writer.write_all(&data[..i])?;
+                written += i as u64;
+                i
+            }
+            None => {
+                writer.write_all(data)?;
+                written += data.len() as u64;
+                data.len()
+            }
+        };
+        window.consume(consumed);
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+// Runs `f` on `data`, with whitespace stripped unless `options` say otherwise. Error offsets are
+// turned back into offsets into `data`.
+pub fn with_stripped<T, F>(data: &[u8], options: Options, f: F) -> Result<T, Error>
+where
+    F: FnOnce(&[u8]) -> Result<T, Error>,
+{
+    if options.keep_whitespace {
+        return f(data);
+    }
+    let (data, whitespace) = whitespace::strip(data);
+    f(&data).map_err(|e| whitespace.original_error(e))
+}
+
+// Computes the decompressed length of `data` without producing any output.
+pub fn decompressed_len(data: &[u8], options: Options) -> Result<u64, Error> {
+    with_stripped(data, options, |data| span_len(data, 0, 0, options))
+}
+
+// Same as `decompressed_len`, but splits the input at top level markers into about as many pieces
+// as there are `threads` and works out the lengths of the pieces in parallel.
+pub fn decompressed_len_parallel(data: &[u8], options: Options, threads: usize) -> Result<u64, Error> {
+    with_stripped(data, options, |data| parallel_len(data, options, threads))
+}
+
+fn parallel_len(data: &[u8], options: Options, threads: usize) -> Result<u64, Error> {
+    if threads < 2 {
+        return span_len(data, 0, 0, options);
+    }
+
+    // Finding the top level tokens only needs to look at the top level markers, so it is cheap
+    // compared to the rest. Whatever is wrong past the last good token is reported after any
+    // error in the tokens before it, just like the sequential version would.
+    let mut tokens = Vec::new();
+    let mut scan_error = None;
+    for token in marker::tokenize(data, 0, options.mode) {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(e) => {
+                scan_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    let piece_size = data.len() / threads + 1;
+    let mut pieces = Vec::new();
+    let mut piece_start = 0;
+    for (i, &(offset, _)) in tokens.iter().enumerate() {
+        if offset - tokens[piece_start].0 >= piece_size as u64 {
+            pieces.push(&tokens[piece_start..i]);
+            piece_start = i;
+        }
+    }
+    pieces.push(&tokens[piece_start..]);
+
+    let lens: Vec<Result<u64, Error>> = thread::scope(|scope| {
+        let handles: Vec<_> = pieces.iter().map(|piece| scope.spawn(move || tokens_len(0, piece, options))).collect();
+        handles.into_iter().map(|h| h.join().expect("Length thread panicked.")).collect()
+    });
+
+    let mut len = 0u64;
+    for (piece, piece_len) in pieces.iter().zip(lens) {
+        match piece_len.ok().and_then(|piece_len| len.checked_add(piece_len)) {
+            Some(sum) => len = sum,
+            // Going over the piece again on top of the pieces before it finds the error the
+            // sequential version would, an overflow may come before the piece's own error.
+            None => return tokens_len(len, piece, options),
+        }
+    }
+    match scan_error {
+        Some(e) => Err(e),
+        None => Ok(len),
+    }
+}
+
+// Adds the decompressed lengths of top level `tokens` to `len`.
+fn tokens_len(mut len: u64, tokens: &[(u64, Token)], options: Options) -> Result<u64, Error> {
+    for &(offset, token) in tokens {
+        let token_len = token_len(offset, token, 0, options)?;
+        len = error::checked(len.checked_add(token_len), offset)?;
+    }
+    Ok(len)
+}
+
+// The decompressed length of `token`, found at `offset` in data nested `depth` deep.
+fn token_len(offset: u64, token: Token, depth: usize, options: Options) -> Result<u64, Error> {
+    match token {
+        Token::Literal(s) => Ok(s.len() as u64),
+        Token::Marker { marker, header, span } => {
+            let span_len = match options.version {
+                Version::One => span.len() as u64,
+                Version::Two => span_len(span, offset + header.len() as u64, nested(depth, offset)?, options)?,
+            };
+            error::checked(span_len.checked_mul(marker.repeat as u64), offset)
+        }
+    }
+}
+
+fn span_len(data: &[u8], offset: u64, depth: usize, options: Options) -> Result<u64, Error> {
+    let mut len = 0u64;
+    for token in marker::tokenize(data, offset, options.mode) {
+        let (offset, token) = token?;
+        let token_len = token_len(offset, token, depth, options)?;
+        len = error::checked(len.checked_add(token_len), offset)?;
+    }
+    Ok(len)
+}
+
+// Same as `decompressed_len`, for when the length doesn't fit in a u64.
+pub fn decompressed_len_big(data: &[u8], options: Options) -> Result<BigUint, Error> {
+    with_stripped(data, options, |data| span_len_big(data, 0, 0, options))
+}
+
+fn span_len_big(data: &[u8], start: u64, depth: usize, options: Options) -> Result<BigUint, Error> {
+    let mut len = BigUint::new(0);
+    let mut tokens = marker::tokenize(data, start, options.mode);
+    while let Some(token) = tokens.next() {
+        let (offset, header_len, repeat, span) = match token {
+            Ok((_, Token::Literal(s))) => {
+                len += &BigUint::new(s.len() as u64);
+                continue;
+            }
+            Ok((offset, Token::Marker { marker, header, span })) => {
+                (offset, header.len(), BigUint::new(marker.repeat as u64), span)
+            }
+            // The tokens stop at a repeat count too large for a `Marker`, which is fine here, and
+            // carry on after its span.
+            Err(Error::TooLarge { offset }) => {
+                let rest = &data[(offset - start) as usize..];
+                match (marker::parse_huge(rest), options.mode) {
+                    (Ok((repeat, n, span)), _) => {
+                        let end = n + span.len();
+                        tokens = marker::tokenize(&rest[end..], offset + end as u64, options.mode);
+                        (offset, n, repeat, span)
+                    }
+                    (Err(Malformed::TooLarge), _) => return Err(Error::TooLarge { offset }),
+                    (Err(_), Mode::Lenient) => {
+                        len += &BigUint::new(1);
+                        tokens = marker::tokenize(&rest[1..], offset + 1, options.mode);
+                        continue;
+                    }
+                    (Err(problem), Mode::Strict) => return Err(Error::Marker { offset, problem }),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        let mut span_len = match options.version {
+            Version::One => BigUint::new(span.len() as u64),
+            Version::Two => span_len_big(span, offset + header_len as u64, nested(depth, offset)?, options)?,
+        };
+        span_len *= &repeat;
+        len += &span_len;
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: [(&str, &str); 6] = [
+        ("ADVENT", "ADVENT"),
+        ("A(1x5)BC", "ABBBBBC"),
+        ("(3x3)XYZ", "XYZXYZXYZ"),
+        ("A(2x2)BCD(2x2)EFG", "ABCBCDEFEFG"),
+        ("(6x1)(1x3)A", "(1x3)A"),
+        ("X(8x2)(3x3)ABCY", "X(3x3)ABC(3x3)ABCY"),
+    ];
+
+    const V2: [(&str, u64); 4] = [
+        ("(3x3)XYZ", 9),
+        ("X(8x2)(3x3)ABCY", 20),
+        ("(27x12)(20x12)(13x14)(7x10)(1x12)A", 241920),
+        ("(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN", 445),
+    ];
+
+    fn options(version: Version, mode: Mode) -> Options {
+        Options {
+            version,
+            mode,
+            ..Options::default()
+        }
+    }
+
+    // Hands out one byte per read, so every marker straddles reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&b, rest)) if !buf.is_empty() => {
+                    buf[0] = b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn decompressed(input: &str, options: Options) -> Result<String, Error> {
+        let mut output = Vec::new();
+        let len = decompress(input.as_bytes(), &mut output, options)?;
+        assert_eq!(len, output.len() as u64);
+        let mut trickled = Vec::new();
+        decompress(Trickle(input.as_bytes()), &mut trickled, options)?;
+        assert_eq!(trickled, output);
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    // The offset and problem of a marker error, from every way of decompressing `input`.
+    fn marker_error(input: &str, options: Options) -> (u64, Malformed) {
+        let errors = [
+            decompressed(input, options).err(),
+            decompressed_len(input.as_bytes(), options).err(),
+            decompressed_len_parallel(input.as_bytes(), options, 3).err(),
+            decompressed_len_big(input.as_bytes(), options).err(),
+        ];
+        let errors: Vec<(u64, Malformed)> = errors
+            .iter()
+            .map(|e| match *e {
+                Some(Error::Marker { offset, problem }) => (offset, problem),
+                ref e => panic!("{:?} gives {:?}", input, e),
+            })
+            .collect();
+        assert!(errors.iter().all(|e| *e == errors[0]), "{:?} gives {:?}", input, errors);
+        errors[0]
+    }
+
+    #[test]
+    fn version_one_examples() {
+        for &(input, output) in &V1 {
+            let options = options(Version::One, Mode::Strict);
+            assert_eq!(decompressed(input, options).unwrap(), output);
+            assert_eq!(decompressed_len(input.as_bytes(), options).unwrap(), output.len() as u64);
+        }
+    }
+
+    #[test]
+    fn version_two_examples() {
+        let options = options(Version::Two, Mode::Strict);
+        assert_eq!(decompressed("X(8x2)(3x3)ABCY", options).unwrap(), "XABCABCABCABCABCABCY");
+        for &(input, len) in &V2 {
+            assert_eq!(decompressed(input, options).unwrap().len() as u64, len);
+            assert_eq!(decompressed_len(input.as_bytes(), options).unwrap(), len);
+            assert_eq!(decompressed_len_parallel(input.as_bytes(), options, 4).unwrap(), len);
+            assert_eq!(decompressed_len_big(input.as_bytes(), options).unwrap(), BigUint::new(len));
+        }
+    }
+
+    #[test]
+    fn whitespace_is_ignored_unless_kept() {
+        let mut options = options(Version::One, Mode::Strict);
+        assert_eq!(decompressed("A(1x\n5)B C\n", options).unwrap(), "ABBBBBC");
+        options.keep_whitespace = true;
+        assert_eq!(decompressed("A(2x2) BC", options).unwrap(), "A B BC");
+    }
+
+    #[test]
+    fn every_malformed_marker_is_an_error_at_its_offset() {
+        for &version in &[Version::One, Version::Two] {
+            let options = options(version, Mode::Strict);
+            assert_eq!(marker_error("ABC(3x3", options), (3, Malformed::Unterminated));
+            assert_eq!(marker_error("A(33)B", options), (1, Malformed::MissingSeparator));
+            assert_eq!(marker_error("(1x2)A(ax2)B", options), (6, Malformed::BadLength));
+            assert_eq!(marker_error("AB(2x)C", options), (2, Malformed::BadRepeat));
+            assert_eq!(marker_error("XY(5x2)AB", options), (2, Malformed::Overrun { len: 5, available: 2 }));
+        }
+    }
+
+    #[test]
+    fn nested_errors_are_at_their_offset() {
+        let options = options(Version::Two, Mode::Strict);
+        assert_eq!(marker_error("A(5x0)(1x9)B", options), (6, Malformed::Overrun { len: 1, available: 0 }));
+        assert_eq!(marker_error("A(9x2)B(5x2)CDE", options), (7, Malformed::Overrun { len: 5, available: 3 }));
+        assert_eq!(marker_error("(6x2)A(3xb)C", options), (6, Malformed::BadRepeat));
+    }
+
+    #[test]
+    fn error_offsets_count_stripped_whitespace() {
+        let options = options(Version::One, Mode::Strict);
+        assert_eq!(marker_error("A B\n C(3xb)", options), (6, Malformed::BadRepeat));
+    }
+
+    #[test]
+    fn huge_markers_are_overruns() {
+        let options = options(Version::One, Mode::Strict);
+        let input = format!("({}x1)A", usize::MAX);
+        assert_eq!(marker_error(&input, options), (0, Malformed::Overrun { len: usize::MAX, available: 1 }));
+        let input = format!("(9999999999999999x2){}", "B".repeat(100_000));
+        let expected = Malformed::Overrun { len: 9999999999999999, available: 100_000 };
+        assert_eq!(marker_error(&input, options), (0, expected));
+    }
+
+    // The offset every way of working out the length of `input` reports it as too large at.
+    fn too_large(input: &str, options: Options) -> u64 {
+        let mut offsets = vec![decompressed_len(input.as_bytes(), options)];
+        for threads in 2..6 {
+            offsets.push(decompressed_len_parallel(input.as_bytes(), options, threads));
+        }
+        let offsets: Vec<u64> = offsets
+            .into_iter()
+            .map(|len| match len {
+                Err(Error::TooLarge { offset }) => offset,
+                len => panic!("{:?} gives {:?}", input, len),
+            })
+            .collect();
+        assert!(offsets.iter().all(|o| *o == offsets[0]), "{:?} gives {:?}", input, offsets);
+        offsets[0]
+    }
+
+    #[test]
+    fn overflow_is_at_the_marker_that_overflows() {
+        let big = "(1x10000000000000000000)A";
+        for &version in &[Version::One, Version::Two] {
+            let options = options(version, Mode::Strict);
+            // The pieces the parallel version splits this into start with a small marker.
+            let input = format!("{}{}{}", big, "(1x1)C".repeat(30), big);
+            assert_eq!(too_large(&input, options), (big.len() + 180) as u64);
+            let input = format!("{}{}{}{}", big, "C".repeat(100), big, "D".repeat(100));
+            assert_eq!(too_large(&input, options), (big.len() + 100) as u64);
+            // An overflow comes before a bad marker after it.
+            let input = format!("{}{}{}(3xb)", big, "C".repeat(100), big);
+            assert_eq!(too_large(&input, options), (big.len() + 100) as u64);
+        }
+        let options = options(Version::Two, Mode::Strict);
+        let input = format!("{}(50x2){}", "C".repeat(40), "(1x10000000000000000000)A".repeat(2));
+        assert_eq!(too_large(&input, options), 40 + 6 + 25);
+    }
+
+    #[test]
+    fn huge_repeat_counts_are_too_large_but_fine_when_big() {
+        let huge = "99999999999999999999";
+        for &version in &[Version::One, Version::Two] {
+            for &mode in &[Mode::Strict, Mode::Lenient] {
+                let options = options(version, mode);
+                let input = format!("A(1x{})B", huge);
+                assert_eq!(too_large(&input, options), 1);
+                match decompressed(&input, options) {
+                    Err(Error::TooLarge { offset: 1 }) => {}
+                    output => panic!("{:?}", output),
+                }
+                assert_eq!(decompressed_len_big(input.as_bytes(), options).unwrap().to_string(), "100000000000000000000");
+                let input = format!("A(2x{})BC(1x2)D", huge);
+                let len = decompressed_len_big(input.as_bytes(), options).unwrap();
+                assert_eq!(len.to_string(), "200000000000000000001");
+            }
+        }
+        let strict = options(Version::Two, Mode::Strict);
+        let input = format!("(25x{})(1x{})A", huge, huge);
+        let len = decompressed_len_big(input.as_bytes(), strict).unwrap();
+        assert_eq!(len.to_string(), "9999999999999999999800000000000000000001");
+        let input = format!("(9x{})AB", huge);
+        let error = decompressed_len_big(input.as_bytes(), strict).unwrap_err();
+        match error {
+            Error::Marker { offset: 0, problem: Malformed::Overrun { len: 9, available: 2 } } => {}
+            e => panic!("{:?}", e),
+        }
+        let lenient = options(Version::Two, Mode::Lenient);
+        let input = format!("(9x{})AB", huge);
+        assert_eq!(decompressed_len_big(input.as_bytes(), lenient).unwrap(), BigUint::new(input.len() as u64));
+        match decompressed_len_big(b"(99999999999999999999999x1)A", lenient) {
+            Err(Error::TooLarge { offset: 0 }) => {}
+            len => panic!("{:?}", len),
+        }
+    }
+
+    #[test]
+    fn lenient_keeps_malformed_markers_as_they_are() {
+        for &version in &[Version::One, Version::Two] {
+            let options = options(version, Mode::Lenient);
+            for &input in &["ABC(3x3", "A(33)B", "(ax2)B", "AB(2x)C", "XY(5x2)AB"] {
+                assert_eq!(decompressed(input, options).unwrap(), input);
+            }
+            assert_eq!(decompressed("(ax2)(1x3)B", options).unwrap(), "(ax2)BBB");
+        }
+        let options = options(Version::Two, Mode::Lenient);
+        assert_eq!(decompressed("(5x2)(3xb)", options).unwrap(), "(3xb)(3xb)");
+        assert_eq!(decompressed_len(b"(5x2)(3xb)", options).unwrap(), 10);
+    }
+
+    #[test]
+    fn nesting_deeper_than_the_limit_is_an_error() {
+        let nest = |depth: usize| {
+            let mut input = String::from("A");
+            for _ in 0..depth {
+                input = format!("({}x1){}", input.len(), input);
+            }
+            input
+        };
+        let options = options(Version::Two, Mode::Strict);
+        assert_eq!(decompressed(&nest(MAX_DEPTH), options).unwrap(), "A");
+        assert_eq!(decompressed_len(nest(MAX_DEPTH).as_bytes(), options).unwrap(), 1);
+        let too_deep = nest(MAX_DEPTH + 1);
+        let errors = [
+            decompressed(&too_deep, options).unwrap_err(),
+            decompressed_len(too_deep.as_bytes(), options).unwrap_err(),
+            decompressed_len_big(too_deep.as_bytes(), options).unwrap_err(),
+        ];
+        for e in &errors {
+            match *e {
+                Error::DepthLimit { limit, .. } => assert_eq!(limit, MAX_DEPTH),
+                ref e => panic!("{:?}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+// What is wrong with a marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    // No ')' before the end of the input, the next '(' or MAX_MARKER_LEN characters.
+    Unterminated,
+    MissingSeparator,
+    BadLength,
+    BadRepeat,
+    // The marker wants more data than there is left of the input, or of the marker it is in.
+    Overrun { len: usize, available: usize },
+    // A length or repeat count too large for a usize. Not wrong as such, so it is reported as
+    // `Error::TooLarge` even when malformed markers are let through.
+    TooLarge,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    // `offset` is the byte offset of the marker's '(' in the input.
+    Marker { offset: u64, problem: Malformed },
+    // The decompressed length doesn't fit in a u64, `offset` is where it first overflowed.
+    TooLarge { offset: u64 },
+    // Limits set for decompressing untrusted input.
+    OutputLimit { len: u64, limit: u64 },
+    DepthLimit { offset: u64, limit: usize },
+    RatioLimit { ratio: f64, limit: f64 },
+}
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Malformed::Unterminated => write!(f, "unterminated marker"),
+            Malformed::MissingSeparator => write!(f, "marker has no 'x'"),
+            Malformed::BadLength => write!(f, "marker length is not a number"),
+            Malformed::BadRepeat => write!(f, "marker repeat count is not a number"),
+            Malformed::Overrun { len, available } => {
+                write!(f, "marker wants {} characters but only {} are left", len, available)
+            }
+            Malformed::TooLarge => write!(f, "marker number too large"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Marker { offset, problem } => write!(f, "byte {}: {}", offset, problem),
+            Error::TooLarge { offset } => write!(f, "byte {}: decompressed output too large", offset),
+            Error::OutputLimit { len, limit } => {
+                write!(f, "decompressed output would be {} bytes, the limit is {}", len, limit)
+            }
+            Error::DepthLimit { offset, limit } => {
+                write!(f, "byte {}: markers nested more than {} deep", offset, limit)
+            }
+            Error::RatioLimit { ratio, limit } => {
+                write!(f, "input would expand {:.1} times, the limit is {}", ratio, limit)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+// Turns the result of a checked length calculation into an error naming `offset` on overflow.
+pub fn checked(len: Option<u64>, offset: u64) -> Result<u64, Error> {
+    len.ok_or(Error::TooLarge { offset })
+}
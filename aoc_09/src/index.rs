@@ -0,0 +1,300 @@
+use std::cmp;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use decompress::{self, Options, Version};
+use error::{self, Error};
+use marker::{self, Token};
+use whitespace;
+
+// A piece of the decompressed output, found at `start` within one copy of whatever it is in.
+struct Node {
+    start: u64,
+    len: u64,
+    kind: Kind,
+}
+
+enum Kind {
+    // Characters copied from `data[from..from + len]`.
+    Literal { from: usize },
+    // `children` repeated, every copy `unit_len` long.
+    Repeat { unit_len: u64, children: Vec<Node> },
+}
+
+// The marker structure of a compressed input with the decompressed length of every part of it,
+// which is enough to find any position in the decompressed output without producing what comes
+// before it.
+pub struct Index {
+    data: Vec<u8>,
+    nodes: Vec<Node>,
+    len: u64,
+}
+
+// Builds the nodes for `data`, found at `offset` and nested `depth` deep.
+fn build(data: &[u8], offset: u64, depth: usize, options: Options) -> Result<(Vec<Node>, u64), Error> {
+    let mut nodes = Vec::new();
+    let mut len = 0;
+    for token in marker::tokenize(data, offset, options.mode) {
+        let (offset, token) = token?;
+        let node = match token {
+            Token::Literal(s) => Node {
+                start: len,
+                len: s.len() as u64,
+                kind: Kind::Literal { from: offset as usize },
+            },
+            Token::Marker { marker, header, span } => {
+                let span_offset = offset + header.len() as u64;
+                let (children, unit_len) = match options.version {
+                    Version::One => {
+                        let literal = Node {
+                            start: 0,
+                            len: span.len() as u64,
+                            kind: Kind::Literal { from: span_offset as usize },
+                        };
+                        (vec![literal], span.len() as u64)
+                    }
+                    Version::Two => build(span, span_offset, decompress::nested(depth, offset)?, options)?,
+                };
+                Node {
+                    start: len,
+                    len: error::checked(unit_len.checked_mul(marker.repeat as u64), offset)?,
+                    kind: Kind::Repeat { unit_len, children },
+                }
+            }
+        };
+        len = error::checked(len.checked_add(node.len), offset)?;
+        nodes.push(node);
+    }
+    Ok((nodes, len))
+}
+
+impl Index {
+    pub fn new(data: &[u8], options: Options) -> Result<Index, Error> {
+        let (data, whitespace) = if options.keep_whitespace {
+            (data.to_vec(), None)
+        } else {
+            let (data, whitespace) = whitespace::strip(data);
+            (data, Some(whitespace))
+        };
+        let (nodes, len) = match (build(&data, 0, 0, options), whitespace) {
+            (Ok(built), _) => built,
+            (Err(e), Some(whitespace)) => return Err(whitespace.original_error(e)),
+            (Err(e), None) => return Err(e),
+        };
+        Ok(Index { data, nodes, len })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn write_nodes<W: Write>(&self, nodes: &[Node], start: u64, end: u64, writer: &mut W) -> io::Result<()> {
+        let first = nodes.partition_point(|n| n.start + n.len <= start);
+        for node in nodes[first..].iter().take_while(|n| n.start < end) {
+            let from = start.saturating_sub(node.start);
+            let to = cmp::min(end - node.start, node.len);
+            match node.kind {
+                Kind::Literal { from: data_from } => {
+                    writer.write_all(&self.data[data_from + from as usize..data_from + to as usize])?;
+                }
+                Kind::Repeat { unit_len, ref children } => {
+                    let mut pos = from;
+                    while pos < to {
+                        let copy = pos - pos % unit_len;
+                        let copy_end = cmp::min(to, copy + unit_len);
+                        self.write_nodes(children, pos - copy, copy_end - copy, writer)?;
+                        pos = copy_end;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Writes bytes `start..end` of the decompressed output, nothing if `end` isn't past `start`.
+    pub fn write_range<W: Write>(&self, start: u64, end: u64, writer: &mut W) -> io::Result<()> {
+        let end = cmp::min(end, self.len);
+        if start >= end {
+            return Ok(());
+        }
+        self.write_nodes(&self.nodes, start, end, writer)
+    }
+}
+
+// Reads the decompressed output of an index from any position.
+pub struct IndexReader<'a> {
+    index: &'a Index,
+    pos: u64,
+}
+
+impl<'a> IndexReader<'a> {
+    pub fn new(index: &'a Index) -> IndexReader<'a> {
+        IndexReader { index, pos: 0 }
+    }
+}
+
+impl<'a> Read for IndexReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = cmp::min(self.pos, self.index.len());
+        let end = cmp::min(self.pos.saturating_add(buf.len() as u64), self.index.len());
+        let n = (end - start) as usize;
+        self.index.write_range(start, end, &mut &mut buf[..n])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<'a> Seek for IndexReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.index.len().checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marker::Mode;
+
+    const V1: [&str; 8] = [
+        "ADVENT",
+        "A(1x5)BC",
+        "X(8x2)(3x3)ABCY",
+        "A(2x2)BCD(2x2)EFG",
+        "(0x5)AB(3x0)XYZC",
+        "(0x0)",
+        "",
+        "A(1x\n5)B C\n",
+    ];
+
+    const V2: [&str; 6] = [
+        "(3x3)XYZ",
+        "X(8x2)(3x3)ABCY",
+        "(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN",
+        "(0x3)A(6x0)(1x9)QB(12x2)(0x4)(2x3)CD",
+        "(10x2)(3x0)ABCDE(1x2)F",
+        "(19x3)(13x2)(2x2)AB(1x0)C",
+    ];
+
+    fn options(version: Version) -> Options {
+        Options {
+            version,
+            ..Options::default()
+        }
+    }
+
+    fn decompressed(input: &str, options: Options) -> Vec<u8> {
+        let mut output = Vec::new();
+        decompress::decompress(input.as_bytes(), &mut output, options).unwrap();
+        output
+    }
+
+    fn range(index: &Index, start: u64, end: u64) -> Vec<u8> {
+        let mut output = Vec::new();
+        index.write_range(start, end, &mut output).unwrap();
+        output
+    }
+
+    // Every range, also ones that start or end past the output, against slicing what
+    // `decompress` writes.
+    fn assert_every_range(input: &str, options: Options) {
+        let expected = decompressed(input, options);
+        let index = Index::new(input.as_bytes(), options).unwrap();
+        assert_eq!(index.len(), expected.len() as u64, "{:?}", input);
+        let len = expected.len();
+        for start in 0..len + 3 {
+            for end in 0..len + 3 {
+                let want = if start < end.min(len) { &expected[start..end.min(len)] } else { &[][..] };
+                assert_eq!(range(&index, start as u64, end as u64), want, "{:?} {}..{}", input, start, end);
+            }
+        }
+        assert_eq!(range(&index, 0, u64::MAX), expected);
+        assert_eq!(range(&index, u64::MAX - 1, u64::MAX), b"");
+    }
+
+    #[test]
+    fn version_one_ranges() {
+        for input in &V1 {
+            assert_every_range(input, options(Version::One));
+        }
+    }
+
+    #[test]
+    fn version_two_ranges() {
+        for input in &V2 {
+            assert_every_range(input, options(Version::Two));
+        }
+    }
+
+    // Too long to try every range of, so only ranges around the copies of the markers.
+    #[test]
+    fn ranges_across_copies() {
+        let input = "(27x12)(20x12)(13x14)(7x10)(1x12)A";
+        let expected = decompressed(input, options(Version::Two));
+        let index = Index::new(input.as_bytes(), options(Version::Two)).unwrap();
+        let len = expected.len() as u64;
+        for &unit in &[1u64, 10, 12, 140, 1680, 20160] {
+            for &copy in &[0, 1, 2, 11] {
+                for &at in &[(copy * unit).saturating_sub(1), copy * unit, copy * unit + 1] {
+                    for &n in &[0, 1, unit - 1, unit, unit + 1, 3 * unit + 2] {
+                        let (start, end) = (at.min(len), (at + n).min(len));
+                        assert_eq!(range(&index, at, at + n), &expected[start as usize..end as usize], "{}+{}", at, n);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_ranges() {
+        let options = Options {
+            version: Version::Two,
+            mode: Mode::Lenient,
+            keep_whitespace: false,
+        };
+        assert_every_range("A(3xb)(5x2)(1x3)AB", options);
+    }
+
+    #[test]
+    fn reads_and_seeks() {
+        let input = V2[2];
+        let expected = decompressed(input, options(Version::Two));
+        let index = Index::new(input.as_bytes(), options(Version::Two)).unwrap();
+        let mut reader = IndexReader::new(&index);
+
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, expected);
+        assert_eq!(reader.read(&mut [0; 8]).unwrap(), 0);
+
+        let mut buf = [0; 7];
+        for start in 0..expected.len() as u64 + 2 {
+            assert_eq!(reader.seek(SeekFrom::Start(start)).unwrap(), start);
+            let n = reader.read(&mut buf).unwrap();
+            let from = cmp::min(start as usize, expected.len());
+            let to = cmp::min(from + 7, expected.len());
+            assert_eq!(&buf[..n], &expected[from..to]);
+        }
+
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), expected.len() as u64 - 3);
+        assert_eq!(reader.seek(SeekFrom::Current(-2)).unwrap(), expected.len() as u64 - 5);
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, &expected[expected.len() - 5..]);
+        assert_eq!(reader.seek(SeekFrom::Current(10)).unwrap(), expected.len() as u64 + 10);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::End(-1000)).is_err());
+        assert!(reader.seek(SeekFrom::Current(-1000)).is_err());
+        // A failed seek stays where it was.
+        assert_eq!(reader.stream_position().unwrap(), expected.len() as u64 + 10);
+    }
+}
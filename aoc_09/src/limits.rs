@@ -0,0 +1,149 @@
+use decompress::{self, Options, Version, MAX_DEPTH};
+use error::Error;
+use marker::{self, Token};
+
+// Limits for decompressing untrusted input, checked before any output is written.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub max_output: Option<u64>,
+    // Never more than MAX_DEPTH, which applies when this is left out.
+    pub max_depth: Option<usize>,
+    // Decompressed length over compressed length.
+    pub max_ratio: Option<f64>,
+}
+
+// Fails on the first marker nested deeper than `max_depth`, `depth` being the nesting depth of
+// `data` itself. Recursion never goes deeper than `max_depth`.
+fn check_depth(data: &[u8], offset: u64, options: Options, depth: usize, max_depth: usize) -> Result<(), Error> {
+    for token in marker::tokenize(data, offset, options.mode) {
+        if let (offset, Token::Marker { header, span, .. }) = token? {
+            if depth + 1 > max_depth {
+                return Err(Error::DepthLimit { offset, limit: max_depth });
+            }
+            if options.version == Version::Two {
+                check_depth(span, offset + header.len() as u64, options, depth + 1, max_depth)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Limits {
+    // Checks `data` against the limits, returning the decompressed length if it is within them.
+    pub fn check(&self, data: &[u8], options: Options) -> Result<u64, Error> {
+        let max_depth = self.max_depth.map_or(MAX_DEPTH, |depth| depth.min(MAX_DEPTH));
+        decompress::with_stripped(data, options, |data| check_depth(data, 0, options, 0, max_depth))?;
+        let len = decompress::decompressed_len(data, options)?;
+        if let Some(limit) = self.max_output {
+            if len > limit {
+                return Err(Error::OutputLimit { len, limit });
+            }
+        }
+        if let Some(limit) = self.max_ratio {
+            let ratio = len as f64 / data.len().max(1) as f64;
+            if ratio > limit {
+                return Err(Error::RatioLimit { ratio, limit });
+            }
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V2: Options = Options {
+        version: Version::Two,
+        mode: marker::Mode::Strict,
+        keep_whitespace: false,
+    };
+
+    fn nest(depth: usize) -> String {
+        let mut input = String::from("A");
+        for _ in 0..depth {
+            input = format!("({}x1){}", input.len(), input);
+        }
+        input
+    }
+
+    fn depth_limit(limits: Limits, data: &str, options: Options) -> (u64, usize) {
+        match limits.check(data.as_bytes(), options) {
+            Err(Error::DepthLimit { offset, limit }) => (offset, limit),
+            len => panic!("{:?} gives {:?}", data, len),
+        }
+    }
+
+    #[test]
+    fn max_output() {
+        let limits = |limit| Limits {
+            max_output: Some(limit),
+            ..Limits::default()
+        };
+        assert_eq!(limits(7).check(b"A(1x5)BC", Options::default()).unwrap(), 7);
+        match limits(6).check(b"A(1x5)BC", Options::default()) {
+            Err(Error::OutputLimit { len: 7, limit: 6 }) => {}
+            len => panic!("{:?}", len),
+        }
+        assert_eq!(limits(241920).check(b"(27x12)(20x12)(13x14)(7x10)(1x12)A", V2).unwrap(), 241920);
+        assert!(limits(241919).check(b"(27x12)(20x12)(13x14)(7x10)(1x12)A", V2).is_err());
+        assert_eq!(limits(0).check(b"", V2).unwrap(), 0);
+    }
+
+    #[test]
+    fn max_depth() {
+        let limits = |limit| Limits {
+            max_depth: Some(limit),
+            ..Limits::default()
+        };
+        assert_eq!(limits(2).check(b"A(6x1)(1x1)BC", V2).unwrap(), 3);
+        assert_eq!(depth_limit(limits(1), "A(6x1)(1x1)BC", V2), (6, 1));
+        assert_eq!(depth_limit(limits(0), "A(6x1)(1x1)BC", V2), (1, 0));
+        // Markers in the data of a marker are plain data in version one.
+        assert_eq!(limits(1).check(b"A(6x1)(1x1)BC", Options::default()).unwrap(), 8);
+        assert_eq!(depth_limit(limits(0), "A(6x1)(1x1)BC", Options::default()), (1, 0));
+    }
+
+    #[test]
+    fn max_depth_is_never_above_the_maximum() {
+        for &limits in &[Limits::default(), Limits { max_depth: Some(usize::MAX), ..Limits::default() }] {
+            assert_eq!(limits.check(nest(MAX_DEPTH).as_bytes(), V2).unwrap(), 1);
+            assert_eq!(depth_limit(limits, &nest(MAX_DEPTH + 1), V2).1, MAX_DEPTH);
+        }
+        let limits = Limits {
+            max_depth: Some(MAX_DEPTH + 1),
+            ..Limits::default()
+        };
+        assert_eq!(depth_limit(limits, &nest(MAX_DEPTH + 1), V2).1, MAX_DEPTH);
+    }
+
+    #[test]
+    fn max_ratio() {
+        let limits = |limit| Limits {
+            max_ratio: Some(limit),
+            ..Limits::default()
+        };
+        // 9 characters out of 6.
+        assert_eq!(limits(1.5).check(b"(1x9)A", Options::default()).unwrap(), 9);
+        match limits(1.4).check(b"(1x9)A", Options::default()) {
+            Err(Error::RatioLimit { ratio, limit }) => assert_eq!((ratio, limit), (1.5, 1.4)),
+            len => panic!("{:?}", len),
+        }
+        // Whitespace counts towards the compressed length.
+        assert_eq!(limits(1.0).check(b"(1x9)A   ", Options::default()).unwrap(), 9);
+        assert_eq!(limits(0.0).check(b"", Options::default()).unwrap(), 0);
+    }
+
+    #[test]
+    fn errors_in_the_input_come_first() {
+        let limits = Limits {
+            max_output: Some(0),
+            max_depth: Some(0),
+            max_ratio: Some(0.0),
+        };
+        match limits.check(b"AB(3x3)C", Options::default()) {
+            Err(Error::Marker { offset: 2, .. }) => {}
+            len => panic!("{:?}", len),
+        }
+    }
+}
@@ -53,48 +53,365 @@
 //
 // What is the decompressed length of the file using this improved format?
 
+mod bench;
+mod bignum;
+mod compress;
+mod decompress;
+mod error;
+mod index;
+mod limits;
+mod marker;
+mod tree;
+mod whitespace;
+
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::{self, BufWriter, SeekFrom};
 use std::path::Path;
+use std::process;
+use std::thread;
 
-fn main() {
-    let prog_name: String = env::args().nth(0).unwrap();
-    if env::args().len() < 2 {
-        println!("{} INPUT", prog_name);
-        return;
-    }
-    let file_name: String = env::args().nth(1).unwrap();
-    let path = Path::new(&file_name);
-    let mut file = File::open(&path).expect("Couldn't open file.");
-
-    let mut compressed = String::new();
-    file.read_to_string(&mut compressed).expect("Failed to read data.");
-    let mut char_itr = compressed.chars();
-
-    let mut decompressed = String::new();
-    let mut input_char = char_itr.next();
-    while let Some(c) = input_char {
-        match c {
-            '(' => {
-                let sub_str_len: usize = char_itr.by_ref()
-                    .take_while(|c| *c != 'x')
-                    .collect::<String>()
-                    .parse()
-                    .unwrap();
-                let repeat: usize = char_itr.by_ref()
-                    .take_while(|c| *c != ')')
-                    .collect::<String>()
-                    .parse()
-                    .unwrap();
-                let sub_str: String = char_itr.by_ref().take(sub_str_len).collect();
-                for _ in 0..repeat {
-                    decompressed += &sub_str;
-                }
+use bignum::BigUint;
+use compress::Strategy;
+use decompress::{Options, Version};
+use error::Error;
+use index::{Index, IndexReader};
+use limits::Limits;
+use marker::Mode;
+use tree::Tree;
+
+// Inputs named - are read from stdin.
+fn usage(prog_name: &str) {
+    eprintln!("{} [--lenient] [--keep-whitespace] [--big] INPUT...", prog_name);
+    eprintln!(
+        "{} decompress [--v2] [--lenient] [--keep-whitespace] [--max-output BYTES] [--max-depth DEPTH] \
+         [--max-ratio RATIO] INPUT [OUTPUT]",
+        prog_name
+    );
+    eprintln!("{} compress [--v2] [--best] INPUT [OUTPUT]", prog_name);
+    eprintln!("{} slice [--v2] [--lenient] [--keep-whitespace] START END INPUT", prog_name);
+    eprintln!("{} tree [--v2] [--lenient] [--keep-whitespace] [--dot] INPUT", prog_name);
+    eprintln!("{} bench [MEGABYTES [THREADS]]", prog_name);
+}
+
+fn fail(name: &str, e: &dyn fmt::Display) -> ! {
+    eprintln!("{}: {}", name, e);
+    process::exit(1);
+}
+
+fn open_input(name: &str) -> io::Result<Box<dyn Read>> {
+    if name == "-" {
+        return Ok(Box::new(io::stdin()));
+    }
+    Ok(Box::new(File::open(Path::new(name))?))
+}
+
+fn read_input(name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    if let Err(e) = open_input(name).and_then(|mut input| input.read_to_end(&mut data)) {
+        fail(name, &e);
+    }
+    data
+}
+
+// Whether `arg` looks like an option rather than a file name, "-" being stdin.
+fn is_flag(arg: &str) -> bool {
+    arg.starts_with("--")
+}
+
+// Applies a flag shared by everything that decompresses, returns false for any other argument.
+fn decompress_flag(options: &mut Options, arg: &str) -> bool {
+    match arg {
+        "--v2" => options.version = Version::Two,
+        "--lenient" => options.mode = Mode::Lenient,
+        "--keep-whitespace" => options.keep_whitespace = true,
+        _ => return false,
+    }
+    true
+}
+
+fn threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Decompressed length of `compressed`, `big` allows lengths that don't fit in a u64.
+fn length(compressed: &[u8], options: Options, big: bool) -> Result<BigUint, Error> {
+    match decompress::decompressed_len_parallel(compressed, options, threads()) {
+        Ok(len) => Ok(BigUint::new(len)),
+        Err(Error::TooLarge { .. }) if big => decompress::decompressed_len_big(compressed, options),
+        Err(e) => Err(e),
+    }
+}
+
+// Prints a table with both decompressed lengths of every file, and their total if there is more
+// than one. Files that fail are reported on stderr, returns false if any did.
+fn print_lengths(file_names: &[&String], mut options: Options, big: bool) -> bool {
+    let width = file_names.iter().map(|f| f.len()).max().unwrap_or(0).max("total".len());
+    println!("{:<w$}  {:>20}  {:>20}", "file", "v1", "v2", w = width);
+
+    let mut total = (BigUint::new(0), BigUint::new(0));
+    let mut ok = true;
+    for file_name in file_names {
+        let mut compressed = Vec::new();
+        if let Err(e) = open_input(file_name).and_then(|mut input| input.read_to_end(&mut compressed)) {
+            eprintln!("{}: {}", file_name, e);
+            ok = false;
+            continue;
+        }
+
+        options.version = Version::One;
+        let v1 = length(&compressed, options, big);
+        options.version = Version::Two;
+        let v2 = length(&compressed, options, big);
+        match (v1, v2) {
+            (Ok(v1), Ok(v2)) => {
+                // BigUint doesn't pad, so format it first.
+                println!("{:<w$}  {:>20}  {:>20}", file_name, v1.to_string(), v2.to_string(), w = width);
+                total.0 += &v1;
+                total.1 += &v2;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("{}: {}", file_name, e);
+                ok = false;
+            }
+        }
+    }
+    if ok && file_names.len() > 1 {
+        println!("{:<w$}  {:>20}  {:>20}", "total", total.0.to_string(), total.1.to_string(), w = width);
+    }
+    ok
+}
+
+// [--lenient] [--keep-whitespace] [--big] INPUT...
+fn run_lengths(args: &[String]) -> bool {
+    let mut options = Options::default();
+    let mut big = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--big" => big = true,
+            // Both lengths are always printed.
+            "--v2" => return false,
+            _ if decompress_flag(&mut options, arg) => (),
+            _ if is_flag(arg) => return false,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return false;
+    }
+    if !print_lengths(&paths, options, big) {
+        process::exit(1);
+    }
+    true
+}
+
+// decompress [--v2] [--lenient] [--keep-whitespace] [--max-output BYTES] [--max-depth DEPTH]
+// [--max-ratio RATIO] INPUT [OUTPUT], writes to stdout if OUTPUT is left out. With any of the
+// limits the input is checked against them before anything is written.
+fn run_decompress(args: &[String]) -> bool {
+    let mut options = Options::default();
+    let mut limits = Limits::default();
+    let mut limited = false;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--max-output" => args.next().and_then(|v| v.parse().ok()).map(|v| limits.max_output = Some(v)),
+            "--max-depth" => args.next().and_then(|v| v.parse().ok()).map(|v| limits.max_depth = Some(v)),
+            "--max-ratio" => args.next().and_then(|v| v.parse().ok()).map(|v| limits.max_ratio = Some(v)),
+            _ if decompress_flag(&mut options, arg) => continue,
+            _ if is_flag(arg) => return false,
+            _ => {
+                paths.push(arg);
+                continue;
             }
-            _ => decompressed.push(c),
+        };
+        if parsed.is_none() {
+            return false;
         }
-        input_char = char_itr.next();
+        limited = true;
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        return false;
+    }
+
+    let mut input = open_input(paths[0]).unwrap_or_else(|e| fail(paths[0], &e));
+    if limited {
+        // Checking the limits needs the whole input, and happens before the output is created.
+        let data = read_input(paths[0]);
+        if let Err(e) = limits.check(&data, options) {
+            fail(paths[0], &e);
+        }
+        input = Box::new(io::Cursor::new(data));
+    }
+    let result = match paths.get(1) {
+        Some(output) => match File::create(Path::new(output)) {
+            Ok(file) => decompress::decompress(input, &mut BufWriter::new(file), options),
+            Err(e) => fail(output, &e),
+        },
+        None => {
+            let stdout = io::stdout();
+            decompress::decompress(input, &mut BufWriter::new(stdout.lock()), options)
+        }
+    };
+    if let Err(e) = result {
+        fail(paths[0], &e);
+    }
+    true
+}
+
+// compress [--v2] [--best] INPUT [OUTPUT], writes to stdout if OUTPUT is left out.
+fn run_compress(args: &[String]) -> bool {
+    let mut version = Version::One;
+    let mut strategy = Strategy::Greedy;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--v2" => version = Version::Two,
+            "--best" => strategy = Strategy::Best,
+            _ if is_flag(arg) => return false,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        return false;
+    }
+
+    let text = read_input(paths[0]);
+    let compressed = match compress::compress(&text, version, strategy) {
+        Ok(compressed) => compressed,
+        Err(e) => fail(paths[0], &e),
+    };
+
+    let mut round_trip = Vec::new();
+    let options = Options {
+        version,
+        keep_whitespace: true,
+        ..Options::default()
+    };
+    match decompress::decompress(&compressed[..], &mut round_trip, options) {
+        Ok(_) if round_trip == text => (),
+        Ok(_) => fail(paths[0], &"compressed data doesn't decompress to the input"),
+        Err(e) => fail(paths[0], &format!("compressed data doesn't decompress: {}", e)),
+    }
+
+    let written = match paths.get(1) {
+        Some(output) => File::create(Path::new(output)).and_then(|mut f| f.write_all(&compressed)),
+        None => io::stdout().write_all(&compressed),
+    };
+    if let Err(e) = written {
+        fail(paths.get(1).map_or("stdout", |p| p.as_str()), &e);
+    }
+    eprintln!("{} -> {} bytes", text.len(), compressed.len());
+    if text.iter().any(|b| b.is_ascii_whitespace()) {
+        eprintln!("Input contains whitespace, decompress with --keep-whitespace.");
+    }
+    true
+}
+
+// slice [--v2] [--lenient] [--keep-whitespace] START END INPUT, writes bytes START..END of the
+// decompressed output to stdout without decompressing anything before them.
+fn run_slice(args: &[String]) -> bool {
+    let mut options = Options::default();
+    let mut rest = Vec::new();
+    for arg in args {
+        if !decompress_flag(&mut options, arg) {
+            if is_flag(arg) {
+                return false;
+            }
+            rest.push(arg);
+        }
+    }
+    let (start, end, file_name): (u64, u64, &str) = match rest[..] {
+        [start, end, file_name] => match (start.parse(), end.parse()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end, file_name),
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    let index = match Index::new(&read_input(file_name), options) {
+        Ok(index) => index,
+        Err(e) => fail(file_name, &e),
+    };
+
+    let mut reader = IndexReader::new(&index);
+    reader.seek(SeekFrom::Start(start)).expect("Failed to seek.");
+    let stdout = io::stdout();
+    if let Err(e) = io::copy(&mut reader.take(end - start), &mut stdout.lock()) {
+        fail("stdout", &e);
+    }
+    true
+}
+
+// tree [--v2] [--lenient] [--keep-whitespace] [--dot] INPUT, prints the markers of INPUT and what
+// each of them adds to the decompressed length.
+fn run_tree(args: &[String]) -> bool {
+    let mut options = Options::default();
+    let mut dot = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        if arg == "--dot" {
+            dot = true;
+        } else if !decompress_flag(&mut options, arg) {
+            if is_flag(arg) {
+                return false;
+            }
+            paths.push(arg);
+        }
+    }
+    if paths.len() != 1 {
+        return false;
+    }
+
+    let tree = match Tree::new(&read_input(paths[0]), options) {
+        Ok(tree) => tree,
+        Err(e) => fail(paths[0], &e),
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = BufWriter::new(stdout.lock());
+    let result = if dot {
+        tree.write_dot(&mut stdout)
+    } else {
+        tree.write_text(&mut stdout)
+    };
+    if let Err(e) = result {
+        fail("stdout", &e);
+    }
+    true
+}
+
+// bench [MEGABYTES [THREADS]], compares the sequential and parallel length computations on made
+// up input.
+fn run_bench(args: &[String]) -> bool {
+    let numbers: Result<Vec<usize>, _> = args.iter().map(|a| a.parse()).collect();
+    let (megabytes, threads) = match numbers.as_ref().map(|n| &n[..]) {
+        Ok([]) => (16, threads()),
+        Ok([megabytes]) => (*megabytes, threads()),
+        Ok([megabytes, threads]) => (*megabytes, *threads),
+        _ => return false,
+    };
+    bench::run(megabytes * 1024 * 1024, threads);
+    true
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let ok = match args.get(1).map(String::as_str) {
+        Some("decompress") => run_decompress(&args[2..]),
+        Some("compress") => run_compress(&args[2..]),
+        Some("slice") => run_slice(&args[2..]),
+        Some("tree") => run_tree(&args[2..]),
+        Some("bench") => run_bench(&args[2..]),
+        Some(_) => run_lengths(&args[1..]),
+        None => false,
+    };
+    if !ok {
+        usage(&args[0]);
+        process::exit(2);
     }
-    println!("Decompressed length: {}", decompressed.trim().len());
 }
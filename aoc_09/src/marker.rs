@@ -0,0 +1,270 @@
+use std::fmt;
+use std::str;
+
+use bignum::BigUint;
+use error::{Error, Malformed};
+
+// Longest marker worth looking for: '(' + two 20 digit numbers + 'x' + ')'.
+pub const MAX_MARKER_LEN: usize = 43;
+
+// A (AxB) marker: repeat the following `len` characters `repeat` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    pub len: usize,
+    pub repeat: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Malformed markers are errors.
+    Strict,
+    // Malformed markers are plain data: the '(' is kept as is and the search for markers
+    // continues right after it.
+    Lenient,
+}
+
+fn is_number(digits: &[u8]) -> bool {
+    !digits.is_empty() && digits.iter().all(|b| b.is_ascii_digit())
+}
+
+// Parses `digits` that `is_number`, which may not fit in a usize.
+fn parse_number(digits: &[u8]) -> Result<usize, Malformed> {
+    str::from_utf8(digits).ok().and_then(|s| s.parse().ok()).ok_or(Malformed::TooLarge)
+}
+
+fn check_numbers(len: &[u8], repeat: &[u8]) -> Result<(), Malformed> {
+    if !is_number(len) {
+        return Err(Malformed::BadLength);
+    }
+    if !is_number(repeat) {
+        return Err(Malformed::BadRepeat);
+    }
+    Ok(())
+}
+
+// Splits the marker at the start of `data` into the digits of its length and repeat count, and
+// the number of bytes it occupies.
+fn split(data: &[u8]) -> Result<(&[u8], &[u8], usize), Malformed> {
+    let end = data
+        .iter()
+        .take(MAX_MARKER_LEN)
+        .skip(1)
+        .position(|b| *b == b')' || *b == b'(')
+        .map(|i| i + 1);
+    let end = match end {
+        Some(end) if data[end] == b')' => end,
+        _ => return Err(Malformed::Unterminated),
+    };
+    let body = &data[1..end];
+    let x = body.iter().position(|b| *b == b'x').ok_or(Malformed::MissingSeparator)?;
+    Ok((&body[..x], &body[x + 1..], end + 1))
+}
+
+impl Marker {
+    // Parses the marker at the start of `data`, returning it together with the number of bytes
+    // it occupies.
+    pub fn parse(data: &[u8]) -> Result<(Marker, usize), Malformed> {
+        let (len, repeat, n) = split(data)?;
+        check_numbers(len, repeat)?;
+        let marker = Marker {
+            len: parse_number(len)?,
+            repeat: parse_number(repeat)?,
+        };
+        Ok((marker, n))
+    }
+}
+
+// Parses the marker at the start of `data` like `Marker::parse`, but with a repeat count of any
+// size. Returns the repeat count, the number of bytes the marker occupies and its span.
+pub fn parse_huge(data: &[u8]) -> Result<(BigUint, usize, &[u8]), Malformed> {
+    let (len, repeat, n) = split(data)?;
+    check_numbers(len, repeat)?;
+    let len = parse_number(len)?;
+    let repeat = BigUint::parse(repeat);
+    let available = data.len() - n;
+    if available < len {
+        return Err(Malformed::Overrun { len, available });
+    }
+    Ok((repeat, n, &data[n..n + len]))
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}x{})", self.len, self.repeat)
+    }
+}
+
+// A piece of compressed data: either plain characters or a marker, as written in the input,
+// together with the span of data it applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    Literal(&'a [u8]),
+    Marker {
+        marker: Marker,
+        header: &'a [u8],
+        span: &'a [u8],
+    },
+}
+
+pub struct Tokens<'a> {
+    data: &'a [u8],
+    offset: u64,
+    mode: Mode,
+}
+
+// Splits `data`, found at `offset` in the input, into tokens.
+pub fn tokenize(data: &[u8], offset: u64, mode: Mode) -> Tokens<'_> {
+    Tokens { data, offset, mode }
+}
+
+impl<'a> Tokens<'a> {
+    fn marker(&self) -> Result<Token<'a>, Malformed> {
+        let (marker, n) = Marker::parse(self.data)?;
+        let available = self.data.len() - n;
+        if available < marker.len {
+            return Err(Malformed::Overrun { len: marker.len, available });
+        }
+        Ok(Token::Marker {
+            marker,
+            header: &self.data[..n],
+            span: &self.data[n..n + marker.len],
+        })
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    // The token and its offset in the input.
+    type Item = Result<(u64, Token<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.data;
+        let token = match data.iter().position(|b| *b == b'(') {
+            _ if data.is_empty() => return None,
+            Some(0) => match (self.marker(), self.mode) {
+                (Ok(token), _) => token,
+                (Err(Malformed::TooLarge), _) => {
+                    self.data = &[];
+                    return Some(Err(Error::TooLarge { offset: self.offset }));
+                }
+                (Err(_), Mode::Lenient) => Token::Literal(&data[..1]),
+                (Err(problem), Mode::Strict) => {
+                    self.data = &[];
+                    return Some(Err(Error::Marker { offset: self.offset, problem }));
+                }
+            },
+            Some(i) => Token::Literal(&data[..i]),
+            None => Token::Literal(data),
+        };
+        let len = match token {
+            Token::Literal(s) => s.len(),
+            Token::Marker { header, span, .. } => header.len() + span.len(),
+        };
+        let offset = self.offset;
+        self.data = &data[len..];
+        self.offset += len as u64;
+        Some(Ok((offset, token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<(Marker, usize), Malformed> {
+        Marker::parse(s.as_bytes())
+    }
+
+    // The error for the first bad token of `s`, as offset and problem.
+    fn first_error(s: &str, mode: Mode) -> Option<(u64, Malformed)> {
+        tokenize(s.as_bytes(), 0, mode).find_map(|token| match token {
+            Err(Error::Marker { offset, problem }) => Some((offset, problem)),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => None,
+        })
+    }
+
+    #[test]
+    fn parses_markers() {
+        assert_eq!(parse("(1x5)BC"), Ok((Marker { len: 1, repeat: 5 }, 5)));
+        assert_eq!(parse("(27x12)"), Ok((Marker { len: 27, repeat: 12 }, 7)));
+        assert_eq!(parse("(0x0)"), Ok((Marker { len: 0, repeat: 0 }, 5)));
+    }
+
+    #[test]
+    fn malformed_markers() {
+        assert_eq!(parse("(3x3"), Err(Malformed::Unterminated));
+        assert_eq!(parse("(3x(3x3)"), Err(Malformed::Unterminated));
+        assert_eq!(parse(&format!("({}x1)", "1".repeat(MAX_MARKER_LEN))), Err(Malformed::Unterminated));
+        assert_eq!(parse("(33)A"), Err(Malformed::MissingSeparator));
+        assert_eq!(parse("(x3)A"), Err(Malformed::BadLength));
+        assert_eq!(parse("(-1x3)A"), Err(Malformed::BadLength));
+        assert_eq!(parse("(3x)A"), Err(Malformed::BadRepeat));
+        assert_eq!(parse("(3x3x3)A"), Err(Malformed::BadRepeat));
+        assert_eq!(parse("(99999999999999999999999x1)"), Err(Malformed::TooLarge));
+        assert_eq!(parse("(1x99999999999999999999)"), Err(Malformed::TooLarge));
+        assert_eq!(parse("(99999999999999999999999xb)"), Err(Malformed::BadRepeat));
+        assert_eq!(parse("(ax99999999999999999999)"), Err(Malformed::BadLength));
+    }
+
+    #[test]
+    fn malformed_tokens_report_their_offset() {
+        assert_eq!(first_error("AB(3x3", Mode::Strict), Some((2, Malformed::Unterminated)));
+        assert_eq!(first_error("ABC(33)A", Mode::Strict), Some((3, Malformed::MissingSeparator)));
+        assert_eq!(first_error("(1x1)A(ax3)A", Mode::Strict), Some((6, Malformed::BadLength)));
+        assert_eq!(first_error("X(3xb)A", Mode::Strict), Some((1, Malformed::BadRepeat)));
+        assert_eq!(first_error("AB(5x2)XY", Mode::Strict), Some((2, Malformed::Overrun { len: 5, available: 2 })));
+    }
+
+    #[test]
+    fn lenient_keeps_the_paren_and_carries_on() {
+        assert_eq!(first_error("AB(5x2)XY(1x2)Z", Mode::Lenient), None);
+        let tokens: Vec<(u64, Token)> = tokenize(b"A(3xb)(1x2)Z", 0, Mode::Lenient).map(Result::unwrap).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, Token::Literal(b"A")),
+                (1, Token::Literal(b"(")),
+                (2, Token::Literal(b"3xb)")),
+                (
+                    6,
+                    Token::Marker {
+                        marker: Marker { len: 1, repeat: 2 },
+                        header: b"(1x2)",
+                        span: b"Z",
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn huge_numbers_are_too_large_in_either_mode() {
+        for &mode in &[Mode::Strict, Mode::Lenient] {
+            let mut tokens = tokenize(b"A(1x99999999999999999999)B", 0, mode);
+            assert!(tokens.next().unwrap().is_ok());
+            match tokens.next() {
+                Some(Err(Error::TooLarge { offset: 1 })) => {}
+                token => panic!("{:?}", token),
+            }
+            assert!(tokens.next().is_none());
+        }
+    }
+
+    #[test]
+    fn huge_repeat_counts() {
+        let (repeat, n, span) = parse_huge(b"(2x99999999999999999999)ABC").unwrap();
+        assert_eq!(repeat.to_string(), "99999999999999999999");
+        assert_eq!((n, span), (24, &b"AB"[..]));
+        assert_eq!(parse_huge(b"(5x99999999999999999999)AB").unwrap_err(), Malformed::Overrun { len: 5, available: 2 });
+        assert_eq!(parse_huge(b"(99999999999999999999x9)AB").unwrap_err(), Malformed::TooLarge);
+        assert_eq!(parse_huge(b"(2xb)AB").unwrap_err(), Malformed::BadRepeat);
+    }
+
+    #[test]
+    fn strict_stops_after_an_error() {
+        let mut tokens = tokenize(b"A(3xb)B", 0, Mode::Strict);
+        assert!(tokens.next().unwrap().is_ok());
+        assert!(tokens.next().unwrap().is_err());
+        assert!(tokens.next().is_none());
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::{self, Write};
+
+use decompress::{self, Options, Version};
+use error::{self, Error};
+use marker::{self, Mode, Token};
+use whitespace::{self, Whitespace};
+
+// Longest bit of a literal shown in the tree.
+const PREVIEW_LEN: usize = 16;
+
+enum Kind {
+    Literal {
+        preview: String,
+    },
+    Marker {
+        header: String,
+        repeat: usize,
+        span: (u64, u64),
+        // Offset of the marker the span ends inside of, if it does.
+        cuts: Option<u64>,
+        children: Vec<Node>,
+    },
+}
+
+// A literal or marker in the compressed input, together with how much it adds to the
+// decompressed output.
+struct Node {
+    offset: u64,
+    len: u64,
+    kind: Kind,
+}
+
+// The marker structure of a compressed input. Offsets are into the input with whitespace still
+// in place.
+pub struct Tree {
+    nodes: Vec<Node>,
+    len: u64,
+    whitespace: Option<Whitespace>,
+}
+
+fn preview(data: &[u8]) -> String {
+    let mut preview = String::from_utf8_lossy(&data[..data.len().min(PREVIEW_LEN)]).into_owned();
+    if data.len() > PREVIEW_LEN {
+        preview.push_str("...");
+    }
+    preview
+}
+
+// Checks whether the span `data[start..end]` ends in the middle of a marker, when reading on
+// past `end` as if the span didn't stop there. Returns the offset of that marker.
+fn find_cut(data: &[u8], start: usize, end: usize, offset: u64) -> Option<u64> {
+    let mut pos = start;
+    for token in marker::tokenize(&data[start..], offset + start as u64, Mode::Lenient) {
+        let (token_offset, token) = token.ok()?;
+        pos += match token {
+            Token::Literal(s) => s.len(),
+            Token::Marker { header, span, .. } => header.len() + span.len(),
+        };
+        if pos >= end {
+            return match token {
+                Token::Marker { .. } if pos > end => Some(token_offset),
+                _ => None,
+            };
+        }
+    }
+    None
+}
+
+// Builds the nodes for `data[start..end]`, `data` being the rest of the input the range is in
+// and found at `offset`. The range is nested `depth` deep.
+fn build(data: &[u8], start: usize, end: usize, offset: u64, depth: usize, options: Options) -> Result<(Vec<Node>, u64), Error> {
+    let mut nodes = Vec::new();
+    let mut len = 0u64;
+    for token in marker::tokenize(&data[start..end], offset + start as u64, options.mode) {
+        let node = match token? {
+            (token_offset, Token::Literal(s)) => Node {
+                offset: token_offset,
+                len: s.len() as u64,
+                kind: Kind::Literal { preview: preview(s) },
+            },
+            (token_offset, Token::Marker { marker, header, span }) => {
+                let span_start = (token_offset - offset) as usize + header.len();
+                let span_end = span_start + span.len();
+                let children = match options.version {
+                    Version::One => vec![Node {
+                        offset: offset + span_start as u64,
+                        len: span.len() as u64,
+                        kind: Kind::Literal { preview: preview(span) },
+                    }],
+                    Version::Two => {
+                        let depth = decompress::nested(depth, token_offset)?;
+                        build(data, span_start, span_end, offset, depth, options)?.0
+                    }
+                };
+                let cuts = find_cut(&data[..end], span_start, span_end, offset);
+                let unit_len = children.iter().try_fold(0u64, |sum, c| sum.checked_add(c.len));
+                let len = unit_len.and_then(|l| l.checked_mul(marker.repeat as u64));
+                Node {
+                    offset: token_offset,
+                    len: error::checked(len, token_offset)?,
+                    kind: Kind::Marker {
+                        header: String::from_utf8_lossy(header).into_owned(),
+                        repeat: marker.repeat,
+                        span: (offset + span_start as u64, offset + span_end as u64),
+                        cuts,
+                        children,
+                    },
+                }
+            }
+        };
+        len = error::checked(len.checked_add(node.len), node.offset)?;
+        nodes.push(node);
+    }
+    Ok((nodes, len))
+}
+
+impl Tree {
+    pub fn new(data: &[u8], options: Options) -> Result<Tree, Error> {
+        if options.keep_whitespace {
+            let (nodes, len) = build(data, 0, data.len(), 0, 0, options)?;
+            return Ok(Tree { nodes, len, whitespace: None });
+        }
+        let (data, whitespace) = whitespace::strip(data);
+        match build(&data, 0, data.len(), 0, 0, options) {
+            Ok((nodes, len)) => Ok(Tree {
+                nodes,
+                len,
+                whitespace: Some(whitespace),
+            }),
+            Err(e) => Err(whitespace.original_error(e)),
+        }
+    }
+
+    fn original_offset(&self, offset: u64) -> u64 {
+        match self.whitespace {
+            Some(ref whitespace) => whitespace.original_offset(offset),
+            None => offset,
+        }
+    }
+
+    fn original_span(&self, span: (u64, u64)) -> (u64, u64) {
+        if span.0 == span.1 {
+            let start = self.original_offset(span.0);
+            return (start, start);
+        }
+        (self.original_offset(span.0), self.original_offset(span.1 - 1) + 1)
+    }
+
+    fn describe(&self, node: &Node) -> String {
+        match node.kind {
+            Kind::Literal { ref preview } => {
+                format!("{:?} @{} -> {}", preview, self.original_offset(node.offset), node.len)
+            }
+            Kind::Marker {
+                ref header,
+                repeat,
+                span,
+                cuts,
+                ..
+            } => {
+                let (start, end) = self.original_span(span);
+                let mut description = format!(
+                    "{} @{} span {}..{} x{} -> {}",
+                    header,
+                    self.original_offset(node.offset),
+                    start,
+                    end,
+                    repeat,
+                    node.len
+                );
+                if let Some(cut) = cuts {
+                    description += &format!(" !! span cuts through marker @{}", self.original_offset(cut));
+                }
+                description
+            }
+        }
+    }
+
+    fn write_text_nodes<W: Write>(&self, nodes: &[Node], depth: usize, writer: &mut W) -> io::Result<()> {
+        for node in nodes {
+            writeln!(writer, "{}{}", "  ".repeat(depth), self.describe(node))?;
+            if let Kind::Marker { ref children, .. } = node.kind {
+                self.write_text_nodes(children, depth + 1, writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the tree as indented text, one node per line.
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "input -> {}", self.len)?;
+        self.write_text_nodes(&self.nodes, 1, writer)
+    }
+
+    // Writes the nodes below `parent` and returns the next free node id.
+    fn write_dot_nodes<W: Write>(&self, nodes: &[Node], parent: usize, mut id: usize, writer: &mut W) -> io::Result<usize> {
+        for node in nodes {
+            let node_id = id;
+            id += 1;
+            let label = self.describe(node).replace('\\', "\\\\").replace('"', "\\\"");
+            match node.kind {
+                Kind::Literal { .. } => writeln!(writer, "    n{} [label=\"{}\", shape=plaintext];", node_id, label)?,
+                Kind::Marker { cuts: Some(_), .. } => {
+                    writeln!(writer, "    n{} [label=\"{}\", color=red, fontcolor=red];", node_id, label)?
+                }
+                Kind::Marker { .. } => writeln!(writer, "    n{} [label=\"{}\"];", node_id, label)?,
+            }
+            writeln!(writer, "    n{} -> n{};", parent, node_id)?;
+            if let Kind::Marker { ref children, .. } = node.kind {
+                id = self.write_dot_nodes(children, node_id, id, writer)?;
+            }
+        }
+        Ok(id)
+    }
+
+    // Writes the tree as a Graphviz digraph.
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "digraph markers {{")?;
+        writeln!(writer, "    node [shape=box];")?;
+        writeln!(writer, "    n0 [label=\"input -> {}\"];", self.len)?;
+        self.write_dot_nodes(&self.nodes, 0, 1, writer)?;
+        writeln!(writer, "}}")
+    }
+}
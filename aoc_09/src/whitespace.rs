@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+
+use error::Error;
+
+// Strips whitespace from compressed data while remembering where it was, so that offsets into
+// the stripped data can be turned back into offsets into the original input.
+pub struct Whitespace {
+    // One entry per run of whitespace: its offset in the stripped data and the total amount of
+    // whitespace removed up to and including the run.
+    runs: VecDeque<(u64, u64)>,
+    // Whitespace removed before the first entry in `runs`.
+    base: u64,
+    removed: u64,
+    kept: u64,
+}
+
+impl Whitespace {
+    pub fn new() -> Whitespace {
+        Whitespace {
+            runs: VecDeque::new(),
+            base: 0,
+            removed: 0,
+            kept: 0,
+        }
+    }
+
+    // Removes whitespace from `data`, the next piece of the input, moving the rest to the front.
+    // Returns the number of bytes kept.
+    pub fn strip(&mut self, data: &mut [u8]) -> usize {
+        let mut kept = 0;
+        for i in 0..data.len() {
+            if !data[i].is_ascii_whitespace() {
+                data[kept] = data[i];
+                kept += 1;
+                continue;
+            }
+            self.removed += 1;
+            let offset = self.kept + kept as u64;
+            match self.runs.back_mut() {
+                Some(run) if run.0 == offset => run.1 = self.removed,
+                _ => self.runs.push_back((offset, self.removed)),
+            }
+        }
+        self.kept += kept as u64;
+        kept
+    }
+
+    // Forgets about whitespace before `offset`, after which only offsets from there on can be
+    // mapped back.
+    pub fn forget(&mut self, offset: u64) {
+        while let Some(&(run, removed)) = self.runs.front() {
+            if run > offset {
+                break;
+            }
+            self.base = removed;
+            self.runs.pop_front();
+        }
+    }
+
+    pub fn original_offset(&self, offset: u64) -> u64 {
+        let i = self.runs.partition_point(|run| run.0 <= offset);
+        let removed = if i == 0 { self.base } else { self.runs[i - 1].1 };
+        offset + removed
+    }
+
+    // Makes the offset of an error point into the original input.
+    pub fn original_error(&self, e: Error) -> Error {
+        match e {
+            Error::Marker { offset, problem } => Error::Marker {
+                offset: self.original_offset(offset),
+                problem,
+            },
+            Error::TooLarge { offset } => Error::TooLarge {
+                offset: self.original_offset(offset),
+            },
+            Error::DepthLimit { offset, limit } => Error::DepthLimit {
+                offset: self.original_offset(offset),
+                limit,
+            },
+            e => e,
+        }
+    }
+}
+
+// Returns `data` with all whitespace removed, together with what is needed to map offsets back.
+pub fn strip(data: &[u8]) -> (Vec<u8>, Whitespace) {
+    let mut stripped = data.to_vec();
+    let mut whitespace = Whitespace::new();
+    let kept = whitespace.strip(&mut stripped);
+    stripped.truncate(kept);
+    (stripped, whitespace)
+}